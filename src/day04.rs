@@ -1,6 +1,8 @@
 use regex::Regex;
 
-type Pair = ((i32, i32), (i32, i32));
+use crate::interval_set::IntervalSet;
+
+type Pair = ((i64, i64), (i64, i64));
 
 pub fn parse(s: &str) -> Vec<Pair> {
     let mut sections = Vec::new();
@@ -9,44 +11,131 @@ pub fn parse(s: &str) -> Vec<Pair> {
         let cap = r.captures(l).unwrap();
         sections.push((
             (
-                cap[1].parse::<i32>().unwrap(),
-                cap[2].parse::<i32>().unwrap(),
+                cap[1].parse::<i64>().unwrap(),
+                cap[2].parse::<i64>().unwrap(),
             ),
             (
-                cap[3].parse::<i32>().unwrap(),
-                cap[4].parse::<i32>().unwrap(),
+                cap[3].parse::<i64>().unwrap(),
+                cap[4].parse::<i64>().unwrap(),
             ),
         ));
     }
-    return sections;
+    sections
 }
 
 pub fn number_fully_contain(pairs: &[Pair]) -> i32 {
     pairs
         .iter()
-        .filter_map(|((a1, a2), (b1, b2))| {
-            if a1 <= b1 && a2 >= b2 || b1 <= a1 && b2 >= a2 {
-                Some(1)
-            } else {
-                None
-            }
+        .filter(|(a, b)| {
+            IntervalSet::from_interval(a.0, a.1).contains_interval(b.0, b.1)
+                || IntervalSet::from_interval(b.0, b.1).contains_interval(a.0, a.1)
         })
-        .sum()
+        .count() as i32
 }
 
 pub fn number_overlaps(pairs: &[Pair]) -> i32 {
     pairs
         .iter()
-        .filter_map(
-            |((a1, a2), (b1, b2))| {
-                if a2 >= b1 && a1 <= b2 {
-                    Some(1)
-                } else {
-                    None
+        .filter(|(a, b)| IntervalSet::from_interval(a.0, a.1).overlaps(b.0, b.1))
+        .count() as i32
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Assignment {
+    pub pair: usize, // Line index.
+    pub elf: usize,  // 0 or 1.
+    pub start: i64,
+    pub end: i64,
+}
+
+// All assignments from all pairs, sorted by start section.
+pub struct AssignmentIndex {
+    assignments: Vec<Assignment>,
+}
+
+impl AssignmentIndex {
+    pub fn new(pairs: &[Pair]) -> Self {
+        let mut assignments: Vec<Assignment> = pairs
+            .iter()
+            .enumerate()
+            .flat_map(|(pair, (a, b))| {
+                [(0, a), (1, b)].map(|(elf, &(start, end))| Assignment {
+                    pair,
+                    elf,
+                    start,
+                    end,
+                })
+            })
+            .collect();
+        assignments.sort_by_key(|a| (a.start, a.end));
+        AssignmentIndex { assignments }
+    }
+
+    pub fn assignments(&self) -> &[Assignment] {
+        &self.assignments
+    }
+
+    // Assignments overlapping [start, end].
+    pub fn overlapping(&self, start: i64, end: i64) -> impl Iterator<Item = &Assignment> {
+        let n = self.assignments.partition_point(|a| a.start <= end);
+        self.assignments[..n].iter().filter(move |a| a.end >= start)
+    }
+
+    // Sweep line, O(n.log(n) + k) where k is the number of overlapping assignments.
+    pub fn overlaps_across_pairs(&self) -> Vec<(Assignment, Assignment)> {
+        let mut overlaps = Vec::new();
+        let mut active: Vec<Assignment> = Vec::new();
+        for a in self.assignments.iter() {
+            active.retain(|other| other.end >= a.start);
+            for other in active.iter() {
+                if other.pair != a.pair {
+                    overlaps.push((*other, *a));
                 }
-            },
-        )
-        .sum()
+            }
+            active.push(*a);
+        }
+        overlaps
+    }
+
+    // Returns the maximum number of elves covering a section and the sections reaching it.
+    pub fn most_covered(&self) -> (usize, IntervalSet) {
+        let mut events: Vec<(i64, i32)> = self
+            .assignments
+            .iter()
+            .flat_map(|a| [(a.start, 1), (a.end + 1, -1)])
+            .collect();
+        events.sort_unstable();
+
+        let mut max = 0;
+        let mut sections = IntervalSet::new();
+        let mut coverage = 0;
+        for (i, (pos, delta)) in events.iter().enumerate() {
+            coverage += delta;
+            match events.get(i + 1) {
+                Some((next_pos, _)) if next_pos > pos && coverage > 0 => {
+                    let c = coverage as usize;
+                    if c > max {
+                        max = c;
+                        sections = IntervalSet::new();
+                    }
+                    if c == max {
+                        sections.insert(*pos, next_pos - 1);
+                    }
+                }
+                _ => (),
+            }
+        }
+        (max, sections)
+    }
+
+    // Sections covered by nobody between the first and the last covered section.
+    pub fn uncovered(&self) -> IntervalSet {
+        let covered: IntervalSet = self.assignments.iter().map(|a| (a.start, a.end)).collect();
+        match (covered.intervals().first(), covered.intervals().last()) {
+            (Some(first), Some(last)) => covered.gaps(first.0, last.1),
+            _ => IntervalSet::new(),
+        }
+    }
 }
 
 #[cfg(test)]
@@ -69,4 +158,22 @@ mod tests {
     fn part2() {
         assert_eq!(number_overlaps(&parse(PAIRS)), 4);
     }
+
+    #[test]
+    fn assignment_index() {
+        let index = AssignmentIndex::new(&parse(PAIRS));
+        assert_eq!(index.overlaps_across_pairs().len(), 45);
+        assert_eq!(index.overlapping(9, 12).count(), 1);
+        let (max, sections) = index.most_covered();
+        assert_eq!(max, 8);
+        assert_eq!(sections.intervals(), &[(6, 6)]);
+        assert!(index.uncovered().is_empty());
+
+        let index = AssignmentIndex::new(&parse("1-2,8-9\n2-3,9-9"));
+        assert_eq!(index.overlaps_across_pairs().len(), 2);
+        let (max, sections) = index.most_covered();
+        assert_eq!(max, 2);
+        assert_eq!(sections.intervals(), &[(2, 2), (9, 9)]);
+        assert_eq!(index.uncovered().intervals(), &[(4, 7)]);
+    }
 }
@@ -1,19 +1,27 @@
-use std::collections::VecDeque;
+use std::{collections::VecDeque, fmt, str::FromStr};
 
+use itertools::Itertools;
 use regex::Regex;
 
 type Stacks = Vec<VecDeque<char>>;
 
+// The stacks are numbered from 1 as in the input, they are checked by 'validate_move'.
 pub struct Move {
     n: usize,
     from: usize,
     to: usize,
 }
 
+impl fmt::Display for Move {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "move {} from {} to {}", self.n, self.from, self.to)
+    }
+}
+
 pub fn parse(s: &str) -> (Stacks, Vec<Move>) {
     let mut stacks = Vec::new();
     let mut lines = s.lines();
-    while let Some(line) = lines.next() {
+    for line in lines.by_ref() {
         let chars: Vec<char> = line.trim_end().chars().collect();
         if chars[1] == '1' {
             break;
@@ -23,10 +31,10 @@ pub fn parse(s: &str) -> (Stacks, Vec<Move>) {
             stacks.push(VecDeque::new());
         }
 
-        for i in 0..n {
+        for (i, stack) in stacks.iter_mut().enumerate().take(n) {
             let pos_char = i * 4 + 1;
             if chars[pos_char] != ' ' {
-                stacks[i].push_front(chars[pos_char]);
+                stack.push_front(chars[pos_char]);
             }
         }
     }
@@ -35,39 +43,242 @@ pub fn parse(s: &str) -> (Stacks, Vec<Move>) {
 
     let r = Regex::new(r"move (\d+) from (\d+) to (\d+)").unwrap();
     let mut moves = Vec::new();
-    while let Some(line) = lines.next() {
+    for line in lines {
         let cap = r.captures(line).unwrap();
         moves.push(Move {
             n: cap[1].parse().unwrap(),
-            from: cap[2].parse::<usize>().unwrap() - 1,
-            to: cap[3].parse::<usize>().unwrap() - 1,
+            from: cap[2].parse().unwrap(),
+            to: cap[3].parse().unwrap(),
         });
     }
 
     (stacks, moves)
 }
 
-pub fn apply_moves_by_crate_mover_9000(stacks: &mut Stacks, moves: &[Move]) {
-    apply_moves::<true>(stacks, moves);
+// A crane model. Moves larger than 'max_lift' are split in several lifts, each lift keeping the
+// order of its crates. With a buffer the crates are first put on an intermediate stack and then
+// lifted again from it to the destination.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Crane {
+    pub max_lift: Option<usize>, // 'None' means no limit.
+    pub buffer: bool,
 }
 
-pub fn apply_moves_by_crate_mover_9001(stacks: &mut Stacks, moves: &[Move]) {
-    apply_moves::<false>(stacks, moves);
+pub const CRATE_MOVER_9000: Crane = Crane {
+    max_lift: Some(1),
+    buffer: false,
+};
+
+pub const CRATE_MOVER_9001: Crane = Crane {
+    max_lift: None,
+    buffer: false,
+};
+
+impl Crane {
+    // Returns the crates in the order they are put on the destination.
+    fn lift(&self, mut crates: VecDeque<char>) -> VecDeque<char> {
+        let mut placed = VecDeque::with_capacity(crates.len());
+        while !crates.is_empty() {
+            let n = self
+                .max_lift
+                .map_or(crates.len(), |max| max.clamp(1, crates.len()));
+            placed.append(&mut crates.split_off(crates.len() - n));
+        }
+        placed
+    }
+
+    pub fn apply_move(&self, stacks: &mut Stacks, m: &Move) {
+        let from = &mut stacks[m.from - 1];
+        let mut crates = self.lift(from.split_off(from.len() - m.n));
+        if self.buffer {
+            crates = self.lift(crates);
+        }
+        stacks[m.to - 1].append(&mut crates);
+    }
 }
 
-fn apply_moves<const REVERSE_STACK: bool>(stacks: &mut Stacks, moves: &[Move]) {
+// Format: "9000", "9001" or "lift=<n|all>[,buffer]".
+impl FromStr for Crane {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "9000" => return Ok(CRATE_MOVER_9000),
+            "9001" => return Ok(CRATE_MOVER_9001),
+            _ => (),
+        }
+
+        let mut crane = CRATE_MOVER_9001;
+        for option in s.split(',') {
+            match option.trim().split_once('=') {
+                Some(("lift", "all")) => crane.max_lift = None,
+                Some(("lift", n)) => match n.parse::<usize>() {
+                    Ok(n) if n > 0 => crane.max_lift = Some(n),
+                    _ => return Err(format!("Invalid lift: {}", n)),
+                },
+                None if option.trim() == "buffer" => crane.buffer = true,
+                _ => return Err(format!("Unknown crane option: {}", option)),
+            }
+        }
+        Ok(crane)
+    }
+}
+
+impl fmt::Display for Crane {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.max_lift {
+            Some(n) => write!(f, "lift={}", n)?,
+            None => write!(f, "lift=all")?,
+        }
+        if self.buffer {
+            write!(f, ",buffer")?;
+        }
+        Ok(())
+    }
+}
+
+pub fn apply_moves(stacks: &mut Stacks, moves: &[Move], crane: &Crane) {
     for m in moves {
-        let from = stacks.get_mut(m.from).unwrap();
-        let mut to_move = from.split_off(from.len() - m.n);
+        crane.apply_move(stacks, m);
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum MoveError {
+    UnknownStack {
+        index: usize,
+        stack: usize,
+    },
+    NotEnoughCrates {
+        index: usize,
+        stack: usize,
+        available: usize,
+        requested: usize,
+    },
+}
+
+impl fmt::Display for MoveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MoveError::UnknownStack { index, stack } => {
+                write!(f, "Move #{}: unknown stack {}", index, stack)
+            }
+            MoveError::NotEnoughCrates {
+                index,
+                stack,
+                available,
+                requested,
+            } => write!(
+                f,
+                "Move #{}: cannot take {} crate(s) from stack {} which holds {}",
+                index, requested, stack, available
+            ),
+        }
+    }
+}
+
+impl std::error::Error for MoveError {}
+
+fn validate_move(stacks: &Stacks, m: &Move, index: usize) -> Result<(), MoveError> {
+    for stack in [m.from, m.to] {
+        if stack == 0 || stack > stacks.len() {
+            return Err(MoveError::UnknownStack { index, stack });
+        }
+    }
+    let available = stacks[m.from - 1].len();
+    if m.n > available {
+        return Err(MoveError::NotEnoughCrates {
+            index,
+            stack: m.from,
+            available,
+            requested: m.n,
+        });
+    }
+    Ok(())
+}
 
-        if REVERSE_STACK {
-            to_move.make_contiguous().reverse();
+// Applies the moves one by one, keeping the state after each of them.
+pub struct Simulator<'a> {
+    moves: &'a [Move],
+    crane: Crane,
+    history: Vec<Stacks>, // 'history[i]' is the state after 'i' moves.
+}
+
+impl<'a> Simulator<'a> {
+    pub fn new(stacks: Stacks, moves: &'a [Move], crane: Crane) -> Self {
+        Simulator {
+            moves,
+            crane,
+            history: vec![stacks],
         }
+    }
 
-        stacks[m.to].append(&mut to_move);
+    pub fn state(&self) -> &Stacks {
+        self.history.last().unwrap()
+    }
+
+    pub fn history(&self) -> &[Stacks] {
+        &self.history
+    }
+
+    pub fn nb_applied(&self) -> usize {
+        self.history.len() - 1
+    }
+
+    // The next move to apply, if any.
+    pub fn next_move(&self) -> Option<&'a Move> {
+        self.moves.get(self.nb_applied())
+    }
+
+    // Returns 'None' when all moves have been applied.
+    pub fn step(&mut self) -> Result<Option<&Stacks>, MoveError> {
+        let index = self.nb_applied();
+        let Some(m) = self.moves.get(index) else {
+            return Ok(None);
+        };
+        let mut stacks = self.state().clone();
+        validate_move(&stacks, m, index)?;
+        self.crane.apply_move(&mut stacks, m);
+        self.history.push(stacks);
+        Ok(Some(self.state()))
+    }
+
+    pub fn run(&mut self) -> Result<&Stacks, MoveError> {
+        while self.step()?.is_some() {}
+        Ok(self.state())
+    }
+
+    // Returns 'None' if there is no move to undo.
+    pub fn undo(&mut self) -> Option<&Stacks> {
+        if self.nb_applied() == 0 {
+            None
+        } else {
+            self.history.pop();
+            Some(self.state())
+        }
     }
 }
 
+// Same format as the puzzle input: '[X]' for each crate, lines padded to the full width and
+// a footer with the stack numbers.
+pub fn to_drawing(stacks: &Stacks) -> String {
+    let height = stacks.iter().map(VecDeque::len).max().unwrap_or(0);
+    let mut drawing = String::new();
+    for level in (0..height).rev() {
+        drawing += &stacks
+            .iter()
+            .map(|stack| match stack.get(level) {
+                Some(c) => format!("[{}]", c),
+                None => String::from("   "),
+            })
+            .join(" ");
+        drawing += "\n";
+    }
+    drawing += &(1..=stacks.len()).map(|i| format!(" {} ", i)).join(" ");
+    drawing += "\n";
+    drawing
+}
+
 pub fn get_top_as_string(stacks: &Stacks) -> String {
     stacks.iter().map_while(VecDeque::back).collect()
 }
@@ -89,14 +300,103 @@ move 1 from 1 to 2";
     #[test]
     fn part1() {
         let (mut stacks, moves) = parse(STACKS_AND_MOVES);
-        apply_moves_by_crate_mover_9000(&mut stacks, &moves);
+        apply_moves(&mut stacks, &moves, &CRATE_MOVER_9000);
         assert_eq!(get_top_as_string(&stacks), "CMZ");
     }
 
     #[test]
     fn part2() {
         let (mut stacks, moves) = parse(STACKS_AND_MOVES);
-        apply_moves_by_crate_mover_9001(&mut stacks, &moves);
+        apply_moves(&mut stacks, &moves, &CRATE_MOVER_9001);
         assert_eq!(get_top_as_string(&stacks), "MCD");
     }
+
+    #[test]
+    fn drawing() {
+        let (mut stacks, moves) = parse(STACKS_AND_MOVES);
+        assert_eq!(
+            to_drawing(&stacks),
+            "    [D]    \n[N] [C]    \n[Z] [M] [P]\n 1   2   3 \n"
+        );
+        assert_eq!(parse(&to_drawing(&stacks)).0, stacks);
+
+        apply_moves(&mut stacks, &moves, &CRATE_MOVER_9000);
+        assert_eq!(
+            to_drawing(&stacks),
+            "        [Z]
+        [N]
+        [D]
+[C] [M] [P]
+ 1   2   3 
+"
+        );
+    }
+
+    #[test]
+    fn simulator() {
+        let (stacks, moves) = parse(STACKS_AND_MOVES);
+        let mut simulator = Simulator::new(stacks, &moves, CRATE_MOVER_9000);
+        assert_eq!(get_top_as_string(simulator.run().unwrap()), "CMZ");
+        assert_eq!(simulator.history().len(), 5);
+        assert_eq!(simulator.step(), Ok(None));
+
+        assert_eq!(get_top_as_string(simulator.undo().unwrap()), "M");
+        simulator.undo();
+        simulator.undo();
+        assert_eq!(get_top_as_string(simulator.undo().unwrap()), "NDP");
+        assert_eq!(simulator.undo(), None);
+        assert_eq!(
+            simulator.next_move().unwrap().to_string(),
+            "move 1 from 2 to 1"
+        );
+
+        let (stacks, moves) = parse(&STACKS_AND_MOVES.replace("move 3 from 1", "move 4 from 1"));
+        let mut simulator = Simulator::new(stacks, &moves, CRATE_MOVER_9000);
+        assert_eq!(
+            simulator.run(),
+            Err(MoveError::NotEnoughCrates {
+                index: 1,
+                stack: 1,
+                available: 3,
+                requested: 4
+            })
+        );
+        assert_eq!(simulator.nb_applied(), 1);
+
+        let (stacks, moves) = parse(&STACKS_AND_MOVES.replace("to 3", "to 4"));
+        assert_eq!(
+            Simulator::new(stacks, &moves, CRATE_MOVER_9000).run(),
+            Err(MoveError::UnknownStack { index: 1, stack: 4 })
+        );
+
+        let (stacks, moves) = parse(&STACKS_AND_MOVES.replace("from 2 to 1", "from 0 to 1"));
+        assert_eq!(
+            Simulator::new(stacks, &moves, CRATE_MOVER_9000).run(),
+            Err(MoveError::UnknownStack { index: 0, stack: 0 })
+        );
+        assert_eq!(
+            MoveError::UnknownStack { index: 0, stack: 0 }.to_string(),
+            "Move #0: unknown stack 0"
+        );
+    }
+
+    #[test]
+    fn cranes() {
+        let (stacks, moves) = parse(STACKS_AND_MOVES);
+        let top_with = |crane: &str| {
+            let mut stacks = stacks.clone();
+            apply_moves(&mut stacks, &moves, &crane.parse().unwrap());
+            get_top_as_string(&stacks)
+        };
+        assert_eq!(top_with("9000"), "CMZ");
+        assert_eq!(top_with("lift=1"), "CMZ");
+        assert_eq!(top_with("9001"), "MCD");
+        assert_eq!(top_with("lift=1,buffer"), "MCD");
+        assert_eq!(top_with("lift=2"), "MCZ");
+        assert_eq!(top_with("lift=2,buffer"), "MCN");
+
+        assert_eq!(CRATE_MOVER_9000.to_string().parse(), Ok(CRATE_MOVER_9000));
+        assert!("lift=0".parse::<Crane>().is_err());
+        assert!("turbo".parse::<Crane>().is_err());
+    }
 }
@@ -1,22 +1,135 @@
-pub fn first_marker_pos(signal: &str, n: usize) -> usize {
-    for (i, c) in signal.chars().collect::<Vec<char>>().windows(n).enumerate() {
-        if !contains_same_char(c) {
-            return i + n;
+use std::{
+    collections::VecDeque,
+    io::{self, BufReader, Read},
+};
+
+// Detects windows of 'size' distinct bytes in a stream, O(1) per byte.
+pub struct MarkerDetector {
+    size: usize,
+    window: VecDeque<u8>,
+    counts: [usize; 256],
+    nb_distinct: usize,
+    position: usize, // Number of bytes read so far.
+}
+
+impl MarkerDetector {
+    pub fn new(size: usize) -> Self {
+        assert!(size > 0, "A marker must have at least one character");
+        MarkerDetector {
+            size,
+            window: VecDeque::with_capacity(size + 1),
+            counts: [0; 256],
+            nb_distinct: 0,
+            position: 0,
         }
     }
-    0
+
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    // Returns the position (number of bytes read) if the last 'size' bytes are all different.
+    pub fn push(&mut self, byte: u8) -> Option<usize> {
+        self.position += 1;
+
+        self.window.push_back(byte);
+        self.counts[byte as usize] += 1;
+        if self.counts[byte as usize] == 1 {
+            self.nb_distinct += 1;
+        }
+
+        if self.window.len() > self.size {
+            let old = self.window.pop_front().unwrap() as usize;
+            self.counts[old] -= 1;
+            if self.counts[old] == 0 {
+                self.nb_distinct -= 1;
+            }
+        }
+
+        if self.nb_distinct == self.size {
+            Some(self.position)
+        } else {
+            None
+        }
+    }
+
+    // Forgets the current window, the position is kept.
+    pub fn clear_window(&mut self) {
+        self.window.clear();
+        self.counts = [0; 256];
+        self.nb_distinct = 0;
+    }
+
+    // Returns the positions of all the markers ending in the given chunk.
+    pub fn feed(&mut self, chunk: &[u8]) -> Vec<usize> {
+        chunk.iter().filter_map(|b| self.push(*b)).collect()
+    }
+}
+
+pub fn first_marker_pos_from_reader<R: Read>(reader: R, n: usize) -> io::Result<Option<usize>> {
+    let mut detector = MarkerDetector::new(n);
+    for byte in BufReader::new(reader).bytes() {
+        if let Some(pos) = detector.push(byte?) {
+            return Ok(Some(pos));
+        }
+    }
+    Ok(None)
+}
+
+pub fn first_marker_pos(signal: &str, n: usize) -> Option<usize> {
+    marker_positions(signal.bytes(), n).next()
+}
+
+pub fn marker_positions(
+    signal: impl IntoIterator<Item = u8>,
+    n: usize,
+) -> impl Iterator<Item = usize> {
+    let mut detector = MarkerDetector::new(n);
+    signal.into_iter().filter_map(move |b| detector.push(b))
+}
+
+pub const PACKET_MARKER_SIZE: usize = 4;
+pub const MESSAGE_MARKER_SIZE: usize = 14;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Marker {
+    Packet(usize),
+    Message(usize),
 }
 
-// Warning: O(n^2).
-fn contains_same_char(chars: &[char]) -> bool {
-    for i in 0..chars.len() {
-        for j in i + 1..chars.len() {
-            if chars[i] == chars[j] {
-                return true;
+// All start-of-packet and start-of-message markers, ordered by position.
+pub fn markers(signal: impl IntoIterator<Item = u8>) -> impl Iterator<Item = Marker> {
+    let mut packet = MarkerDetector::new(PACKET_MARKER_SIZE);
+    let mut message = MarkerDetector::new(MESSAGE_MARKER_SIZE);
+    signal.into_iter().flat_map(move |b| {
+        [
+            packet.push(b).map(Marker::Packet),
+            message.push(b).map(Marker::Message),
+        ]
+        .into_iter()
+        .flatten()
+    })
+}
+
+// Splits the signal into the messages following each start-of-message marker. A marker can't
+// overlap the previous one, each message ends where the next marker begins.
+pub fn split_messages(signal: &[u8]) -> Vec<&[u8]> {
+    let mut detector = MarkerDetector::new(MESSAGE_MARKER_SIZE);
+    let mut messages = Vec::new();
+    let mut start: Option<usize> = None;
+    for b in signal {
+        if let Some(pos) = detector.push(*b) {
+            if let Some(start) = start {
+                messages.push(&signal[start..pos - MESSAGE_MARKER_SIZE]);
             }
+            start = Some(pos);
+            detector.clear_window();
         }
     }
-    false
+    if let Some(start) = start {
+        messages.push(&signal[start..]);
+    }
+    messages
 }
 
 #[cfg(test)]
@@ -25,22 +138,84 @@ mod tests {
 
     #[test]
     fn part1() {
-        assert_eq!(first_marker_pos("mjqjpqmgbljsphdztnvjfqwrcgsmlb", 4), 7);
-        assert_eq!(first_marker_pos("bvwbjplbgvbhsrlpgdmjqwftvncz", 4), 5);
-        assert_eq!(first_marker_pos("nppdvjthqldpwncqszvftbrmjlhg", 4), 6);
-        assert_eq!(first_marker_pos("nznrnfrfntjfmvfwmzdfjlvtqnbhcprsg", 4), 10);
-        assert_eq!(first_marker_pos("zcfzfwzzqfrljwzlrfnpqdbhtmscgvjw", 4), 11);
+        assert_eq!(
+            first_marker_pos("mjqjpqmgbljsphdztnvjfqwrcgsmlb", 4),
+            Some(7)
+        );
+        assert_eq!(first_marker_pos("bvwbjplbgvbhsrlpgdmjqwftvncz", 4), Some(5));
+        assert_eq!(first_marker_pos("nppdvjthqldpwncqszvftbrmjlhg", 4), Some(6));
+        assert_eq!(
+            first_marker_pos("nznrnfrfntjfmvfwmzdfjlvtqnbhcprsg", 4),
+            Some(10)
+        );
+        assert_eq!(
+            first_marker_pos("zcfzfwzzqfrljwzlrfnpqdbhtmscgvjw", 4),
+            Some(11)
+        );
     }
 
     #[test]
     fn part2() {
-        assert_eq!(first_marker_pos("mjqjpqmgbljsphdztnvjfqwrcgsmlb", 14), 19);
-        assert_eq!(first_marker_pos("bvwbjplbgvbhsrlpgdmjqwftvncz", 14), 23);
-        assert_eq!(first_marker_pos("nppdvjthqldpwncqszvftbrmjlhg", 14), 23);
+        assert_eq!(
+            first_marker_pos("mjqjpqmgbljsphdztnvjfqwrcgsmlb", 14),
+            Some(19)
+        );
+        assert_eq!(
+            first_marker_pos("bvwbjplbgvbhsrlpgdmjqwftvncz", 14),
+            Some(23)
+        );
+        assert_eq!(
+            first_marker_pos("nppdvjthqldpwncqszvftbrmjlhg", 14),
+            Some(23)
+        );
         assert_eq!(
             first_marker_pos("nznrnfrfntjfmvfwmzdfjlvtqnbhcprsg", 14),
-            29
+            Some(29)
+        );
+        assert_eq!(
+            first_marker_pos("zcfzfwzzqfrljwzlrfnpqdbhtmscgvjw", 14),
+            Some(26)
+        );
+    }
+
+    #[test]
+    fn streaming() {
+        let mut detector = MarkerDetector::new(4);
+        assert_eq!(detector.feed(b"mjqj"), vec![]);
+        assert_eq!(detector.feed(b"pqmg"), vec![7, 8]);
+        assert_eq!(detector.position(), 8);
+
+        assert_eq!(
+            first_marker_pos_from_reader("zcfzfwzzqfrljwzlrfnpqdbhtmscgvjw".as_bytes(), 14)
+                .unwrap(),
+            Some(26)
+        );
+        assert_eq!(
+            first_marker_pos_from_reader("aaaaaa".as_bytes(), 2).unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn all_markers() {
+        assert_eq!(first_marker_pos("aaaaaa", 4), None);
+
+        let markers: Vec<Marker> = markers("bvwbjplbgvbhsrlpgdmjqwftvncz".bytes()).collect();
+        assert_eq!(markers[0], Marker::Packet(5));
+        assert_eq!(
+            markers.iter().find(|m| matches!(m, Marker::Message(_))),
+            Some(&Marker::Message(23))
+        );
+        assert_eq!(
+            marker_positions("abcdd".bytes(), 3).collect::<Vec<usize>>(),
+            vec![3, 4]
         );
-        assert_eq!(first_marker_pos("zcfzfwzzqfrljwzlrfnpqdbhtmscgvjw", 14), 26);
+    }
+
+    #[test]
+    fn messages() {
+        let signal = b"aabcdefghijklmndcbaabcdefghijklmnkk";
+        assert_eq!(split_messages(signal), vec![&b"dcba"[..], &b"kk"[..]]);
+        assert!(split_messages(b"aaaa").is_empty());
     }
 }
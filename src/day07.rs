@@ -1,32 +1,335 @@
-use std::str::Lines;
+use std::{collections::BTreeMap, fmt};
+
+use itertools::Itertools;
+
+pub type DirId = usize;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Entry {
+    Dir(DirId),
+    File(i64),
+}
 
 #[derive(Debug)]
 pub struct Dir {
-    files: Vec<i64>,
-    dirs: Vec<Dir>,
+    name: String,
+    parent: Option<DirId>,
+    files: BTreeMap<String, i64>,
+    dirs: BTreeMap<String, DirId>,
 }
 
 impl Dir {
-    fn new() -> Dir {
+    fn new(name: &str, parent: Option<DirId>) -> Dir {
         Dir {
-            files: Vec::new(),
-            dirs: Vec::new(),
+            name: name.to_string(),
+            parent,
+            files: BTreeMap::new(),
+            dirs: BTreeMap::new(),
         }
     }
 
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn parent(&self) -> Option<DirId> {
+        self.parent
+    }
+
+    pub fn files(&self) -> &BTreeMap<String, i64> {
+        &self.files
+    }
+
+    pub fn dirs(&self) -> &BTreeMap<String, DirId> {
+        &self.dirs
+    }
+
     fn get_file_size(&self) -> i64 {
-        self.files.iter().sum()
+        self.files.values().sum()
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct CleanupPlan {
+    pub paths: Vec<String>,
+    pub freed: i64,
+}
+
+// All directories are stored in a vector and refer to each other by index, the root is the
+// first one.
+#[derive(Debug)]
+pub struct FileSystem {
+    dirs: Vec<Dir>,
+}
+
+impl FileSystem {
+    pub const ROOT: DirId = 0;
+
+    fn new() -> Self {
+        FileSystem {
+            dirs: vec![Dir::new("", None)],
+        }
+    }
+
+    pub fn dir(&self, id: DirId) -> &Dir {
+        &self.dirs[id]
+    }
+
+    fn get_or_create_dir(&mut self, parent: DirId, name: &str) -> DirId {
+        if let Some(id) = self.dirs[parent].dirs.get(name) {
+            return *id;
+        }
+        let id = self.dirs.len();
+        self.dirs.push(Dir::new(name, Some(parent)));
+        self.dirs[parent].dirs.insert(name.to_string(), id);
+        id
+    }
+
+    // Absolute path of a directory, the root is "/".
+    pub fn path(&self, id: DirId) -> String {
+        let mut names = Vec::new();
+        let mut current = id;
+        while let Some(parent) = self.dirs[current].parent {
+            names.push(self.dirs[current].name.as_str());
+            current = parent;
+        }
+        names.reverse();
+        format!("/{}", names.join("/"))
+    }
+
+    // Absolute path like "/a/e/i".
+    pub fn lookup(&self, path: &str) -> Option<Entry> {
+        let mut current = FileSystem::ROOT;
+        let mut names = path.split('/').filter(|n| !n.is_empty()).peekable();
+        while let Some(name) = names.next() {
+            let dir = &self.dirs[current];
+            match dir.dirs.get(name) {
+                Some(id) => current = *id,
+                None if names.peek().is_none() => {
+                    return dir.files.get(name).map(|size| Entry::File(*size))
+                }
+                None => return None,
+            }
+        }
+        Some(Entry::Dir(current))
+    }
+
+    // Recursive size of each directory, indexed by 'DirId'. A directory is always created after
+    // its parent so sizes can be accumulated in reverse order, O(n).
+    pub fn sizes(&self) -> Vec<i64> {
+        let mut sizes: Vec<i64> = self.dirs.iter().map(Dir::get_file_size).collect();
+        for id in (1..self.dirs.len()).rev() {
+            let parent = self.dirs[id].parent.unwrap();
+            sizes[parent] += sizes[id];
+        }
+        sizes
+    }
+
+    // Paths of the directories whose size satisfies the predicate.
+    pub fn find<P>(&self, predicate: P) -> Vec<String>
+    where
+        P: Fn(i64) -> bool,
+    {
+        self.sizes()
+            .iter()
+            .enumerate()
+            .filter_map(|(id, size)| {
+                if predicate(*size) {
+                    Some(self.path(id))
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+
+    // Like 'du': recursive size of each directory, the largest first.
+    pub fn du(&self) -> Vec<(String, i64)> {
+        self.sizes()
+            .iter()
+            .enumerate()
+            .map(|(id, size)| (self.path(id), *size))
+            .sorted_by(|(p1, s1), (p2, s2)| s2.cmp(s1).then(p1.cmp(p2)))
+            .collect()
+    }
+
+    // Like the puzzle description with the size of the directories added:
+    // - / (dir, size=48381165)
+    //   - a (dir, size=94853)
+    //     - e (dir, size=584)
+    //       - i (file, size=584)
+    pub fn to_tree(&self) -> String {
+        fn write_dir(fs: &FileSystem, sizes: &[i64], id: DirId, depth: usize, tree: &mut String) {
+            let dir = &fs.dirs[id];
+            let name = if id == FileSystem::ROOT {
+                "/"
+            } else {
+                &dir.name
+            };
+            *tree += &format!(
+                "{}- {} (dir, size={})\n",
+                "  ".repeat(depth),
+                name,
+                sizes[id]
+            );
+
+            let entries = dir
+                .dirs
+                .iter()
+                .map(|(name, id)| (name, Entry::Dir(*id)))
+                .merge_by(
+                    dir.files
+                        .iter()
+                        .map(|(name, size)| (name, Entry::File(*size))),
+                    |(n1, _), (n2, _)| n1 <= n2,
+                );
+            for (name, entry) in entries {
+                match entry {
+                    Entry::Dir(child) => write_dir(fs, sizes, child, depth + 1, tree),
+                    Entry::File(size) => {
+                        *tree += &format!(
+                            "{}- {} (file, size={})\n",
+                            "  ".repeat(depth + 1),
+                            name,
+                            size
+                        )
+                    }
+                }
+            }
+        }
+
+        let mut tree = String::new();
+        write_dir(self, &self.sizes(), FileSystem::ROOT, 0, &mut tree);
+        tree
+    }
+
+    // Canonical '$ cd' / '$ ls' session, entries sorted by name. 'parse' gives back the same tree.
+    pub fn to_transcript(&self) -> String {
+        fn write_dir(fs: &FileSystem, id: DirId, transcript: &mut String) {
+            let dir = &fs.dirs[id];
+            *transcript += "$ ls\n";
+            for name in dir.dirs.keys() {
+                *transcript += &format!("dir {}\n", name);
+            }
+            for (name, size) in dir.files.iter() {
+                *transcript += &format!("{} {}\n", size, name);
+            }
+            for (name, child) in dir.dirs.iter() {
+                *transcript += &format!("$ cd {}\n", name);
+                write_dir(fs, *child, transcript);
+                *transcript += "$ cd ..\n";
+            }
+        }
+
+        let mut transcript = String::from("$ cd /\n");
+        write_dir(self, FileSystem::ROOT, &mut transcript);
+        transcript
+    }
+
+    // Chooses non-nested directories to delete so that at least 'required' space is free on a disk
+    // of size 'capacity', deleting as little as possible. Returns 'None' if it's impossible.
+    //
+    // The directories are visited in preorder, skipping a directory goes to its first child and
+    // deleting it jumps after its subtree. The reachable amounts are kept in a bitset bounded by the
+    // smallest directory big enough, O(n.bound/64) in time. The memory is O(bound) for the
+    // reconstruction plus a bitset per level of the tree.
+    pub fn plan_cleanup(&self, capacity: i64, required: i64) -> Option<CleanupPlan> {
+        // 'before' holds the amounts reachable when entering the subtree of 'id', deleting 'id' adds
+        // its size to them as it can't be combined with deletions inside its subtree.
+        fn visit(
+            fs: &FileSystem,
+            sizes: &[i64],
+            id: DirId,
+            before: &[u64],
+            reachable: &mut [u64],
+            reached_by: &mut [u32],
+        ) {
+            for (i, child) in fs.dirs[id].dirs.values().enumerate() {
+                if i == 0 {
+                    // Nothing has been reached since entering 'id'.
+                    visit(fs, sizes, *child, before, reachable, reached_by);
+                } else {
+                    let snapshot = reachable.to_vec();
+                    visit(fs, sizes, *child, &snapshot, reachable, reached_by);
+                }
+            }
+
+            let (words, bits) = (sizes[id] as usize / 64, sizes[id] as usize % 64);
+            for w in (words..reachable.len()).rev() {
+                let src = w - words;
+                let mut shifted = before[src] << bits;
+                if bits != 0 && src > 0 {
+                    shifted |= before[src - 1] >> (64 - bits);
+                }
+                let mut new = shifted & !reachable[w];
+                reachable[w] |= shifted;
+                while new != 0 {
+                    reached_by[w * 64 + new.trailing_zeros() as usize] = id as u32;
+                    new &= new - 1;
+                }
+            }
+        }
+
+        let sizes = self.sizes();
+        let to_free = required - (capacity - sizes[FileSystem::ROOT]);
+        if to_free <= 0 {
+            return Some(CleanupPlan {
+                paths: Vec::new(),
+                freed: 0,
+            });
+        }
+
+        let bound = *sizes.iter().filter(|size| **size >= to_free).min()? as usize;
+        let mut reachable = vec![0u64; bound / 64 + 1];
+        reachable[0] = 1;
+        // The directory which made each amount reachable for the first time, the bits beyond
+        // 'bound' are ignored.
+        let mut reached_by = vec![u32::MAX; reachable.len() * 64];
+        let before = reachable.clone();
+        visit(
+            self,
+            &sizes,
+            FileSystem::ROOT,
+            &before,
+            &mut reachable,
+            &mut reached_by,
+        );
+
+        let freed = (to_free as usize..=bound)
+            .find(|amount| reachable[amount / 64] & (1 << (amount % 64)) != 0)?;
+
+        let mut ids = Vec::new();
+        let mut amount = freed;
+        while amount > 0 {
+            let id = reached_by[amount] as DirId;
+            ids.push(id);
+            amount -= sizes[id] as usize;
+        }
+        ids.sort_by_key(|id| self.path(*id));
+
+        Some(CleanupPlan {
+            paths: ids.iter().map(|id| self.path(*id)).collect(),
+            freed: freed as i64,
+        })
     }
 
     pub fn dir_sizes<P>(&self, predicate: P, result: &mut Vec<i64>) -> i64
     where
         P: Fn(i64) -> bool + Copy,
     {
-        let size = self.get_file_size()
-            + self
+        self.dir_sizes_from(FileSystem::ROOT, predicate, result)
+    }
+
+    fn dir_sizes_from<P>(&self, id: DirId, predicate: P, result: &mut Vec<i64>) -> i64
+    where
+        P: Fn(i64) -> bool + Copy,
+    {
+        let dir = &self.dirs[id];
+        let size = dir.get_file_size()
+            + dir
                 .dirs
-                .iter()
-                .map(|dir| dir.dir_sizes(predicate, result))
+                .values()
+                .map(|child| self.dir_sizes_from(*child, predicate, result))
                 .sum::<i64>();
 
         if predicate(size) {
@@ -36,29 +339,151 @@ impl Dir {
     }
 }
 
-pub fn parse(input: &str) -> Dir {
-    fn create_dir(lines: &mut Lines) -> Dir {
-        let mut dir = Dir::new();
-        while let Some(l) = lines.next() {
-            let l: Vec<&str> = l.split(' ').collect();
-            if l[0] == "$" {
-                if l[1] == "cd" {
-                    if l[2] == ".." {
-                        return dir;
-                    } else {
-                        let child = create_dir(lines);
-                        dir.dirs.push(child);
-                    }
+// Two file systems are equal if they have the same tree, whatever the order of the session.
+impl PartialEq for FileSystem {
+    fn eq(&self, other: &Self) -> bool {
+        fn same_dir(fs1: &FileSystem, id1: DirId, fs2: &FileSystem, id2: DirId) -> bool {
+            let (dir1, dir2) = (&fs1.dirs[id1], &fs2.dirs[id2]);
+            dir1.files == dir2.files
+                && dir1.dirs.len() == dir2.dirs.len()
+                && dir1
+                    .dirs
+                    .iter()
+                    .zip(dir2.dirs.iter())
+                    .all(|((n1, c1), (n2, c2))| n1 == n2 && same_dir(fs1, *c1, fs2, *c2))
+        }
+        same_dir(self, FileSystem::ROOT, other, FileSystem::ROOT)
+    }
+}
+
+impl Eq for FileSystem {}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Change {
+    AddedDir(String),
+    RemovedDir(String),
+    ResizedDir(String, i64, i64),
+    AddedFile(String, i64),
+    RemovedFile(String, i64),
+    ResizedFile(String, i64, i64),
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Change::AddedDir(path) => write!(f, "+ {} (dir)", path),
+            Change::RemovedDir(path) => write!(f, "- {} (dir)", path),
+            Change::ResizedDir(path, old, new) => {
+                write!(f, "~ {} (dir, size={} -> {})", path, old, new)
+            }
+            Change::AddedFile(path, size) => write!(f, "+ {} (file, size={})", path, size),
+            Change::RemovedFile(path, size) => write!(f, "- {} (file, size={})", path, size),
+            Change::ResizedFile(path, old, new) => {
+                write!(f, "~ {} (file, size={} -> {})", path, old, new)
+            }
+        }
+    }
+}
+
+// Changes to go from 'old' to 'new'. The content of an added or removed directory isn't listed,
+// a directory is resized when its recursive size changes.
+pub fn diff(old: &FileSystem, new: &FileSystem) -> Vec<Change> {
+    fn diff_dir(
+        old: &FileSystem,
+        old_sizes: &[i64],
+        old_id: DirId,
+        new: &FileSystem,
+        new_sizes: &[i64],
+        new_id: DirId,
+        changes: &mut Vec<Change>,
+    ) {
+        let (old_dir, new_dir) = (&old.dirs[old_id], &new.dirs[new_id]);
+        let path = |name: &str| {
+            if new_id == FileSystem::ROOT {
+                format!("/{}", name)
+            } else {
+                format!("{}/{}", new.path(new_id), name)
+            }
+        };
+
+        if old_sizes[old_id] != new_sizes[new_id] {
+            changes.push(Change::ResizedDir(
+                new.path(new_id),
+                old_sizes[old_id],
+                new_sizes[new_id],
+            ));
+        }
+
+        for name in old_dir
+            .files
+            .keys()
+            .chain(new_dir.files.keys())
+            .sorted()
+            .dedup()
+        {
+            match (old_dir.files.get(name), new_dir.files.get(name)) {
+                (Some(s1), Some(s2)) if s1 != s2 => {
+                    changes.push(Change::ResizedFile(path(name), *s1, *s2))
+                }
+                (Some(s1), None) => changes.push(Change::RemovedFile(path(name), *s1)),
+                (None, Some(s2)) => changes.push(Change::AddedFile(path(name), *s2)),
+                _ => (),
+            }
+        }
+
+        for name in old_dir
+            .dirs
+            .keys()
+            .chain(new_dir.dirs.keys())
+            .sorted()
+            .dedup()
+        {
+            match (old_dir.dirs.get(name), new_dir.dirs.get(name)) {
+                (Some(id1), Some(id2)) => {
+                    diff_dir(old, old_sizes, *id1, new, new_sizes, *id2, changes)
                 }
-            } else if l[0] != "dir" {
-                dir.files.push(l[0].parse().unwrap());
+                (Some(_), None) => changes.push(Change::RemovedDir(path(name))),
+                (None, Some(_)) => changes.push(Change::AddedDir(path(name))),
+                (None, None) => (),
+            }
+        }
+    }
+
+    let mut changes = Vec::new();
+    diff_dir(
+        old,
+        &old.sizes(),
+        FileSystem::ROOT,
+        new,
+        &new.sizes(),
+        FileSystem::ROOT,
+        &mut changes,
+    );
+    changes
+}
+
+pub fn parse(input: &str) -> FileSystem {
+    let mut fs = FileSystem::new();
+    let mut cwd = FileSystem::ROOT;
+    for l in input.lines() {
+        let l: Vec<&str> = l.split_whitespace().collect();
+        match l[..] {
+            ["$", "cd", "/"] => cwd = FileSystem::ROOT,
+            ["$", "cd", ".."] => cwd = fs.dirs[cwd].parent.unwrap_or(FileSystem::ROOT),
+            ["$", "cd", name] => cwd = fs.get_or_create_dir(cwd, name),
+            ["$", "ls"] => (),
+            ["dir", name] => {
+                fs.get_or_create_dir(cwd, name);
             }
+            [size, name] => {
+                let size = size.parse().expect("Can't parse file size");
+                fs.dirs[cwd].files.insert(name.to_string(), size);
+            }
+            [] => (),
+            _ => panic!("Unknown line: {}", l.join(" ")),
         }
-        dir
     }
-    let mut lines = input.lines();
-    lines.next(); // First line is always the root.
-    create_dir(&mut lines)
+    fs
 }
 
 #[cfg(test)]
@@ -106,4 +531,121 @@ $ ls
         root.dir_sizes(|size| size >= to_free, &mut sizes);
         assert_eq!(*sizes.iter().min().unwrap(), 2_493_3642);
     }
+
+    #[test]
+    fn names_and_paths() {
+        let fs = parse(INPUT);
+        let Some(Entry::Dir(e)) = fs.lookup("/a/e") else {
+            panic!("'/a/e' not found");
+        };
+        assert_eq!(fs.path(e), "/a/e");
+        assert_eq!(fs.dir(e).name(), "e");
+        assert_eq!(fs.lookup("/a/e/i"), Some(Entry::File(584)));
+        assert_eq!(fs.lookup("/"), Some(Entry::Dir(FileSystem::ROOT)));
+        assert_eq!(fs.lookup("/a/x"), None);
+        assert_eq!(fs.lookup("/b.txt/x"), None);
+
+        // 'cd /' in the middle and a repeated 'ls' must not change anything.
+        let fs2 = parse(&format!("{}\n$ cd /\n$ cd a\n$ ls\ndir e\n29116 f", INPUT));
+        assert_eq!(
+            fs2.dir_sizes(|_| false, &mut Vec::new()),
+            fs.dir_sizes(|_| false, &mut Vec::new())
+        );
+    }
+
+    #[test]
+    fn reports() {
+        let fs = parse(INPUT);
+        assert_eq!(
+            fs.to_tree(),
+            "- / (dir, size=48381165)
+  - a (dir, size=94853)
+    - e (dir, size=584)
+      - i (file, size=584)
+    - f (file, size=29116)
+    - g (file, size=2557)
+    - h.lst (file, size=62596)
+  - b.txt (file, size=14848514)
+  - c.dat (file, size=8504156)
+  - d (dir, size=24933642)
+    - d.ext (file, size=5626152)
+    - d.log (file, size=8033020)
+    - j (file, size=4060174)
+    - k (file, size=7214296)
+"
+        );
+        assert_eq!(
+            fs.du(),
+            vec![
+                (String::from("/"), 48_381_165),
+                (String::from("/d"), 24_933_642),
+                (String::from("/a"), 94_853),
+                (String::from("/a/e"), 584),
+            ]
+        );
+        assert_eq!(fs.find(|size| size <= 100_000), vec!["/a", "/a/e"]);
+    }
+
+    #[test]
+    fn cleanup_planner() {
+        let fs = parse(INPUT);
+        assert_eq!(
+            fs.plan_cleanup(70_000_000, 30_000_000),
+            Some(CleanupPlan {
+                paths: vec![String::from("/d")],
+                freed: 24_933_642
+            })
+        );
+        // 'b.txt' and 'c.dat' can't be deleted alone, '/a' and '/d' together are the best.
+        assert_eq!(
+            fs.plan_cleanup(48_381_165, 25_000_000),
+            Some(CleanupPlan {
+                paths: vec![String::from("/a"), String::from("/d")],
+                freed: 25_028_495
+            })
+        );
+        assert_eq!(
+            fs.plan_cleanup(50_000_000, 1_000_000),
+            Some(CleanupPlan {
+                paths: vec![],
+                freed: 0
+            })
+        );
+        assert_eq!(fs.plan_cleanup(50_000_000, 60_000_000), None);
+    }
+
+    #[test]
+    fn transcript_and_diff() {
+        let fs = parse(INPUT);
+        let transcript = fs.to_transcript();
+        assert!(transcript.starts_with("$ cd /\n$ ls\ndir a\ndir d\n14848514 b.txt\n"));
+        assert_eq!(parse(&transcript), fs);
+        assert_eq!(parse(&transcript).to_transcript(), transcript);
+
+        let modified = parse(
+            &INPUT
+                .replace("29116 f", "30000 f")
+                .replace("584 i", "584 i\n10 x")
+                .replace("$ cd d\n$ ls\n", "$ cd d\n$ ls\ndir z\n")
+                .replace("7214296 k", ""),
+        );
+        assert_ne!(modified, fs);
+        assert_eq!(
+            diff(&fs, &modified)
+                .iter()
+                .map(Change::to_string)
+                .collect::<Vec<String>>(),
+            vec![
+                "~ / (dir, size=48381165 -> 41167763)",
+                "~ /a (dir, size=94853 -> 95747)",
+                "~ /a/f (file, size=29116 -> 30000)",
+                "~ /a/e (dir, size=584 -> 594)",
+                "+ /a/e/x (file, size=10)",
+                "~ /d (dir, size=24933642 -> 17719346)",
+                "- /d/k (file, size=7214296)",
+                "+ /d/z (dir)",
+            ]
+        );
+        assert!(diff(&fs, &fs).is_empty());
+    }
 }
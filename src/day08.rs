@@ -1,7 +1,6 @@
-use itertools::{
-    FoldWhile::{Continue, Done},
-    Itertools,
-};
+use std::cmp::Reverse;
+
+use itertools::Itertools;
 
 #[derive(Clone, Copy)]
 enum Orientation {
@@ -11,6 +10,18 @@ enum Orientation {
     South,
 }
 
+impl Orientation {
+    // Step (di, dj) going away from the side.
+    fn step(self) -> (i64, i64) {
+        match self {
+            Orientation::West => (0, 1),
+            Orientation::North => (1, 0),
+            Orientation::Est => (0, -1),
+            Orientation::South => (-1, 0),
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct Matrix<T>(Vec<Vec<T>>);
 
@@ -26,17 +37,30 @@ where
         Matrix(m)
     }
 
+    // The matrix seen from the given side: (0, 0) is the left corner of this side and 'j' goes
+    // away from it.
     fn rotate(&self, i: usize, j: usize, o: Orientation) -> (usize, usize) {
         match o {
             Orientation::West => (i, j),
-            Orientation::North => (j, self.height() - i - 1),
+            Orientation::North => (j, self.width() - i - 1),
             Orientation::Est => (self.height() - i - 1, self.width() - j - 1),
-            Orientation::South => (self.width() - j - 1, i),
+            Orientation::South => (self.height() - j - 1, i),
         }
     }
 
-    fn get(&self, i: usize, j: usize) -> &T {
-        &self.0[i][j]
+    // Size of the matrix seen from the given side.
+    fn height_orientation(&self, o: Orientation) -> usize {
+        match o {
+            Orientation::West | Orientation::Est => self.height(),
+            Orientation::North | Orientation::South => self.width(),
+        }
+    }
+
+    fn width_orientation(&self, o: Orientation) -> usize {
+        match o {
+            Orientation::West | Orientation::Est => self.width(),
+            Orientation::North | Orientation::South => self.height(),
+        }
     }
 
     fn get_orientation(&self, i: usize, j: usize, o: Orientation) -> &T {
@@ -71,96 +95,223 @@ pub fn parse(input: &str) -> Matrix<i32> {
     Matrix(m)
 }
 
+const ORIENTATIONS: [Orientation; 4] = [
+    Orientation::West,
+    Orientation::North,
+    Orientation::Est,
+    Orientation::South,
+];
+
 // O(n).
-pub fn number_of_visible_trees(forest: &Matrix<i32>) -> i32 {
-    let h = forest.height();
-    let w = forest.width();
-
-    let mut visibility = Matrix::<bool>::new(h, w);
-    let mut nb_visible_tree = 0;
-
-    for o in [
-        Orientation::West,
-        Orientation::North,
-        Orientation::Est,
-        Orientation::South,
-    ] {
-        for i in 0..h {
+pub fn visibility_map(forest: &Matrix<i32>) -> Matrix<bool> {
+    let mut visibility = Matrix::<bool>::new(forest.height(), forest.width());
+
+    for o in ORIENTATIONS {
+        for i in 0..forest.height_orientation(o) {
             let mut max = -1;
-            for j in 0..w {
+            for j in 0..forest.width_orientation(o) {
                 let tree_height = forest.get_orientation(i, j, o);
                 if *tree_height > max {
-                    if !visibility.get_orientation(i, j, o) {
-                        visibility.set_orientation(i, j, o, true);
-                        nb_visible_tree += 1
-                    }
+                    visibility.set_orientation(i, j, o, true);
                     max = *tree_height;
                 }
             }
         }
     }
-    nb_visible_tree
+    visibility
+}
+
+pub fn number_of_visible_trees(forest: &Matrix<i32>) -> i32 {
+    visibility_map(forest)
+        .0
+        .iter()
+        .flat_map(|row| row.iter())
+        .filter(|visible| **visible)
+        .count() as i32
+}
+
+// Scenic score of each tree, the trees on the edges have a score of 0. For each side a monotonic
+// stack keeps the trees which can still block the view, O(n).
+pub fn scenic_scores(forest: &Matrix<i32>) -> Matrix<i32> {
+    let mut scores = Matrix::<i32>::new(forest.height(), forest.width());
+    for row in scores.0.iter_mut() {
+        row.fill(1);
+    }
+
+    for o in ORIENTATIONS {
+        for i in 0..forest.height_orientation(o) {
+            let mut stack: Vec<(i32, usize)> = Vec::new(); // Tree heights with their position.
+            for j in 0..forest.width_orientation(o) {
+                let tree_height = *forest.get_orientation(i, j, o);
+                while stack.last().is_some_and(|(h, _)| *h < tree_height) {
+                    stack.pop();
+                }
+                let view_distance = match stack.last() {
+                    Some((_, j2)) => j - j2,
+                    None => j,
+                };
+                let score = *scores.get_orientation(i, j, o);
+                scores.set_orientation(i, j, o, score * view_distance as i32);
+                stack.push((tree_height, j));
+            }
+        }
+    }
+    scores
 }
 
 pub fn best_scenic_score(forest: &Matrix<i32>) -> i32 {
-    let h = forest.height();
-    let w = forest.width();
-
-    let mut current_best_score = -1;
-
-    for i in 1..h - 1 {
-        for j in 1..w - 1 {
-            let current = forest.get(i, j);
-            let dist_w = (1..j)
-                .rev()
-                .fold_while(1, |dist, j2| {
-                    if forest.get(i, j2) >= current {
-                        Done(dist)
-                    } else {
-                        Continue(dist + 1)
-                    }
-                })
-                .into_inner();
-
-            let dist_n = (1..i)
-                .rev()
-                .fold_while(1, |dist, i2| {
-                    if forest.get(i2, j) >= current {
-                        Done(dist)
-                    } else {
-                        Continue(dist + 1)
-                    }
-                })
-                .into_inner();
-
-            let dist_e = (j + 1..w - 1)
-                .fold_while(1, |dist, j2| {
-                    if forest.get(i, j2) >= current {
-                        Done(dist)
-                    } else {
-                        Continue(dist + 1)
-                    }
-                })
-                .into_inner();
-
-            let dist_s = (i + 1..h - 1)
-                .fold_while(1, |dist, i2| {
-                    if forest.get(i2, j) >= current {
-                        Done(dist)
-                    } else {
-                        Continue(dist + 1)
-                    }
-                })
-                .into_inner();
-
-            let score = dist_w * dist_n * dist_e * dist_s;
-            if score > current_best_score {
-                current_best_score = score;
+    let scores = scenic_scores(forest);
+    best_tree(&scores).map_or(0, |(i, j)| scores.0[i][j])
+}
+
+// Position of the tree with the highest score.
+pub fn best_tree(scores: &Matrix<i32>) -> Option<(usize, usize)> {
+    (0..scores.height())
+        .flat_map(|i| (0..scores.width()).map(move |j| (i, j)))
+        .max_by_key(|(i, j)| (scores.0[*i][*j], Reverse((*i, *j))))
+}
+
+// Can be outside the forest, 'eye_height' is compared to the tree heights.
+#[derive(Debug, Clone, Copy)]
+pub struct Observer {
+    pub i: i64,
+    pub j: i64,
+    pub eye_height: i32,
+}
+
+const DIAGONAL_STEPS: [(i64, i64); 4] = [(-1, -1), (-1, 1), (1, 1), (1, -1)];
+
+// Trees seen by the observer looking in the four cardinal directions and optionally the diagonals.
+// There is no perspective as in the puzzle: a tree is hidden by a tree in between if the latter is
+// at least as high as both the tree and the eyes of the observer.
+pub fn visible_trees(
+    forest: &Matrix<i32>,
+    observer: &Observer,
+    with_diagonals: bool,
+) -> Vec<(usize, usize)> {
+    let (h, w) = (forest.height() as i64, forest.width() as i64);
+    let steps = ORIENTATIONS
+        .map(Orientation::step)
+        .into_iter()
+        .chain(DIAGONAL_STEPS.into_iter().filter(|_| with_diagonals));
+
+    // Bounds of the steps 'k >= 1' for which 'p + k * dp' is in '0..size', the first one is above
+    // the last one if the ray misses it. Computed in 'i128' so an observer near the bounds of 'i64'
+    // doesn't overflow.
+    let in_forest = |p: i64, dp: i64, size: i64| {
+        let (p, size) = (p as i128, size as i128);
+        match dp {
+            0 if (0..size).contains(&p) => (1, i128::MAX),
+            0 => (1, 0),
+            1 => ((-p).max(1), size - 1 - p),
+            _ => ((p - size + 1).max(1), p),
+        }
+    };
+
+    let mut visible = Vec::new();
+    for (di, dj) in steps {
+        let (first_i, last_i) = in_forest(observer.i, di, h);
+        let (first_j, last_j) = in_forest(observer.j, dj, w);
+        let mut max = -1; // Highest tree between the observer and the current position.
+        for k in first_i.max(first_j)..=last_i.min(last_j) {
+            let i = (observer.i as i128 + k * di as i128) as usize;
+            let j = (observer.j as i128 + k * dj as i128) as usize;
+            let tree_height = forest.0[i][j];
+            if max < tree_height.max(observer.eye_height) {
+                visible.push((i, j));
             }
+            max = max.max(tree_height);
         }
     }
+    visible.sort();
+    visible
+}
+
+pub type Rgb = (u8, u8, u8);
+
+const MARK_COLOR: Rgb = (255, 0, 0);
+
+pub fn visibility_color(visible: &bool) -> Rgb {
+    if *visible {
+        (40, 200, 40)
+    } else {
+        (20, 50, 20)
+    }
+}
+
+// From black for 0 to yellow for 'max'.
+pub fn heat_color(max: i32) -> impl Fn(&i32) -> Rgb {
+    move |v| {
+        let t = if max > 0 { *v as f64 / max as f64 } else { 0.0 };
+        (
+            (255.0 * (2.0 * t).min(1.0)) as u8,
+            (255.0 * (2.0 * t - 1.0).max(0.0)) as u8,
+            0,
+        )
+    }
+}
+
+// Plain grayscale PGM (P2), values are scaled to [0, 255].
+pub fn to_pgm(m: &Matrix<i32>) -> String {
+    let max = m.0.iter().flatten().copied().max().unwrap_or(0).max(1);
+    let mut pgm = format!("P2\n{} {}\n255\n", m.width(), m.height());
+    for row in m.0.iter() {
+        pgm += &row.iter().map(|v| (v * 255 / max).to_string()).join(" ");
+        pgm += "\n";
+    }
+    pgm
+}
+
+// Plain color PPM (P3), the marked position is drawn in red.
+pub fn to_ppm<T, F>(m: &Matrix<T>, color: F, marked: Option<(usize, usize)>) -> String
+where
+    T: Default + Clone,
+    F: Fn(&T) -> Rgb,
+{
+    let mut ppm = format!("P3\n{} {}\n255\n", m.width(), m.height());
+    for (i, row) in m.0.iter().enumerate() {
+        ppm += &row
+            .iter()
+            .enumerate()
+            .map(|(j, v)| {
+                let (r, g, b) = if marked == Some((i, j)) {
+                    MARK_COLOR
+                } else {
+                    color(v)
+                };
+                format!("{} {} {}", r, g, b)
+            })
+            .join(" ");
+        ppm += "\n";
+    }
+    ppm
+}
 
-    current_best_score
+// The forest heights printed over a background colored from 'm', for a terminal supporting
+// 24-bit colors.
+pub fn to_ansi<T, F>(
+    forest: &Matrix<i32>,
+    m: &Matrix<T>,
+    color: F,
+    marked: Option<(usize, usize)>,
+) -> String
+where
+    T: Default + Clone,
+    F: Fn(&T) -> Rgb,
+{
+    let mut ansi = String::new();
+    for i in 0..forest.height() {
+        for j in 0..forest.width() {
+            let (r, g, b) = if marked == Some((i, j)) {
+                MARK_COLOR
+            } else {
+                color(&m.0[i][j])
+            };
+            ansi += &format!("\x1b[48;2;{};{};{}m{}", r, g, b, forest.0[i][j]);
+        }
+        ansi += "\x1b[0m\n";
+    }
+    ansi
 }
 
 #[cfg(test)]
@@ -184,4 +335,107 @@ mod tests {
         let forest = parse(FOREST);
         assert_eq!(best_scenic_score(&forest), 8)
     }
+
+    #[test]
+    fn scores() {
+        let forest = parse(FOREST);
+        let scores = scenic_scores(&forest);
+        assert_eq!(scores.0[1][2], 4);
+        assert_eq!(scores.0[3][2], 8);
+        assert_eq!(scores.0[0], vec![0; 5]);
+
+        // Not a square.
+        let forest = parse(
+            "3037
+            2551
+            6533",
+        );
+        assert_eq!(number_of_visible_trees(&forest), 12);
+        assert_eq!(scenic_scores(&forest).0[1], vec![0, 1, 1, 0]);
+    }
+
+    #[test]
+    fn images() {
+        let forest = parse(FOREST);
+        let scores = scenic_scores(&forest);
+        assert_eq!(best_tree(&scores), Some((3, 2)));
+
+        let pgm = to_pgm(&scores);
+        assert!(pgm.starts_with("P2\n5 5\n255\n0 0 0 0 0\n0 31 127 31 0\n"));
+
+        let ppm = to_ppm(
+            &visibility_map(&forest),
+            visibility_color,
+            best_tree(&scores),
+        );
+        let lines: Vec<&str> = ppm.lines().collect();
+        assert_eq!(lines[..3], ["P3", "5 5", "255"]);
+        assert_eq!(lines[5], "40 200 40 40 200 40 20 50 20 40 200 40 40 200 40");
+        assert!(lines[6].contains("20 50 20 255 0 0 20 50 20"));
+
+        let ansi = to_ansi(&forest, &scores, heat_color(8), None);
+        assert_eq!(ansi.lines().count(), 5);
+        assert!(ansi.starts_with("\x1b[48;2;0;0;0m3"));
+    }
+
+    #[test]
+    fn observers() {
+        let forest = parse(FOREST);
+        let on_ground = |i, j| Observer {
+            i,
+            j,
+            eye_height: -1,
+        };
+        assert_eq!(
+            visible_trees(&forest, &on_ground(1, -1), false),
+            vec![(1, 0), (1, 1)]
+        );
+        assert_eq!(
+            visible_trees(&forest, &on_ground(-3, 2), false),
+            vec![(0, 2), (1, 2)]
+        );
+        assert!(visible_trees(&forest, &on_ground(-1, -1), false).is_empty());
+        assert_eq!(
+            visible_trees(&forest, &on_ground(-1, -1), true),
+            vec![(0, 0), (1, 1)]
+        );
+
+        let giant = Observer {
+            i: 1,
+            j: -1,
+            eye_height: 6,
+        };
+        assert_eq!(visible_trees(&forest, &giant, false).len(), 5);
+
+        // In a treehouse on the best tree.
+        let treehouse = Observer {
+            i: 3,
+            j: 2,
+            eye_height: 6,
+        };
+        assert_eq!(visible_trees(&forest, &treehouse, false).len(), 8);
+        assert_eq!(visible_trees(&forest, &treehouse, true).len(), 14);
+
+        // Far away, only the rays going through the forest are followed.
+        assert_eq!(
+            visible_trees(&forest, &on_ground(-1_000_000_000, 2), false),
+            vec![(0, 2), (1, 2)]
+        );
+        assert_eq!(
+            visible_trees(&forest, &on_ground(-1_000_000_002, -1_000_000_000), true),
+            vec![(0, 2)]
+        );
+        assert_eq!(
+            visible_trees(&forest, &on_ground(i64::MIN, i64::MIN), true),
+            vec![(0, 0), (1, 1)]
+        );
+        assert_eq!(
+            visible_trees(&forest, &on_ground(i64::MAX, 2), true),
+            vec![(3, 2), (4, 2)]
+        );
+        assert_eq!(
+            visible_trees(&forest, &on_ground(2, i64::MAX), true),
+            vec![(2, 0), (2, 1), (2, 3), (2, 4)]
+        );
+    }
 }
@@ -1,69 +1,313 @@
-use std::collections::HashSet;
+use std::{collections::HashSet, fmt};
 
-enum Direction {
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
     Left,
     Up,
     Right,
     Down,
+    UpLeft,
+    UpRight,
+    DownLeft,
+    DownRight,
 }
 
+impl Direction {
+    fn parse(s: &str) -> Option<Self> {
+        Some(match s {
+            "L" => Direction::Left,
+            "U" => Direction::Up,
+            "R" => Direction::Right,
+            "D" => Direction::Down,
+            "UL" | "LU" => Direction::UpLeft,
+            "UR" | "RU" => Direction::UpRight,
+            "DL" | "LD" => Direction::DownLeft,
+            "DR" | "RD" => Direction::DownRight,
+            _ => return None,
+        })
+    }
+
+    // Up is toward negative y.
+    fn delta(&self) -> (i32, i32) {
+        match self {
+            Direction::Left => (-1, 0),
+            Direction::Up => (0, -1),
+            Direction::Right => (1, 0),
+            Direction::Down => (0, 1),
+            Direction::UpLeft => (-1, -1),
+            Direction::UpRight => (1, -1),
+            Direction::DownLeft => (-1, 1),
+            Direction::DownRight => (1, 1),
+        }
+    }
+}
+
+impl fmt::Display for Direction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            Direction::Left => "L",
+            Direction::Up => "U",
+            Direction::Right => "R",
+            Direction::Down => "D",
+            Direction::UpLeft => "UL",
+            Direction::UpRight => "UR",
+            Direction::DownLeft => "DL",
+            Direction::DownRight => "DR",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Movement {
     direction: Direction,
-    distance: i32,
+    distance: u32,
+}
+
+impl Movement {
+    pub fn distance(&self) -> u32 {
+        self.distance
+    }
+}
+
+impl fmt::Display for Movement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.direction, self.distance)
+    }
 }
 
 pub fn parse(input: &str) -> Vec<Movement> {
-    input
-        .lines()
-        .map(|l| {
-            let split: Vec<&str> = l.trim().split(' ').collect();
-            Movement {
-                direction: match split[0] {
-                    "L" => Direction::Left,
-                    "U" => Direction::Up,
-                    "R" => Direction::Right,
-                    "D" => Direction::Down,
-                    other => panic!("Uknown movement: {}", other),
-                },
-                distance: split[1].parse().expect("Can't parse distance"),
-            }
-        })
-        .collect()
+    parse_script(input).unwrap()
 }
 
-pub fn nb_positions_visited_by_tail<const N: usize>(movements: &[Movement]) -> usize {
-    let mut visited: HashSet<(i32, i32)> = HashSet::new();
-    visited.insert((0, 0));
+// Each line is one or more movements separated by commas, like "R 4, UL 2". A line like
+// "(R 1, U 1) * 3" repeats its movements.
+pub fn parse_script(input: &str) -> Result<Vec<Movement>, String> {
+    fn parse_movement(s: &str) -> Option<Movement> {
+        let (direction, distance) = s.trim().split_once(' ')?;
+        Some(Movement {
+            direction: Direction::parse(direction)?,
+            distance: distance.trim().parse().ok()?,
+        })
+    }
 
-    let mut rope = [(0, 0); N]; // First element is the tail, last element is the head.
+    let mut movements = Vec::new();
+    for (n, l) in input.lines().enumerate() {
+        let l = l.trim();
+        if l.is_empty() {
+            continue;
+        }
 
-    for m in movements {
-        for _ in 0..m.distance {
-            // 1) Move the head.
-            let h = &mut rope[N - 1];
-            *h = match m.direction {
-                Direction::Left => (h.0 - 1, h.1),
-                Direction::Up => (h.0, h.1 - 1),
-                Direction::Right => (h.0 + 1, h.1),
-                Direction::Down => (h.0, h.1 + 1),
-            };
-
-            // 2) Move the rest of the rope.
-            for i in (0..N - 1).rev() {
-                let target = rope[i + 1];
-                let node = &mut rope[i];
-
-                let (dx, dy): (i32, i32) = (target.0 - node.0, target.1 - node.1);
-
-                if dx.abs() >= 2 || dy.abs() >= 2 {
-                    node.0 += dx.signum();
-                    node.1 += dy.signum();
-                    if i == 0 {
-                        visited.insert(*node);
+        let (segments, repeat) = match l.strip_prefix('(').and_then(|l| l.split_once(')')) {
+            Some((segments, repeat)) => {
+                let repeat = repeat
+                    .trim()
+                    .strip_prefix('*')
+                    .map(|r| r.trim().parse::<usize>());
+                match repeat {
+                    Some(Ok(repeat)) => (segments, repeat),
+                    _ => return Err(format!("Line {}: invalid repetition: {}", n + 1, l)),
+                }
+            }
+            None => (l, 1),
+        };
+
+        let segments = segments
+            .split(',')
+            .map(|s| {
+                parse_movement(s).ok_or(format!("Line {}: unknown movement: {}", n + 1, s.trim()))
+            })
+            .collect::<Result<Vec<Movement>, String>>()?;
+
+        for _ in 0..repeat {
+            movements.extend_from_slice(&segments);
+        }
+    }
+    Ok(movements)
+}
+
+// How a knot follows the one ahead of it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FollowRule {
+    // As in the puzzle: one step on each axis toward the knot ahead when it isn't touching.
+    Signum,
+    // Like 'Signum' but the rope can stretch: the knot only moves when the one ahead is more than
+    // 'slack' + 1 cells away.
+    Slack(u32),
+    // Horizontal or vertical steps, on the axis where the knot ahead is the farthest, until it's
+    // touching again. A diagonal move of the knot ahead needs two steps.
+    Cardinal,
+}
+
+impl FollowRule {
+    fn follow(&self, node: Position, target: Position) -> Position {
+        let (dx, dy) = (target.0 - node.0, target.1 - node.1);
+        let distance = dx.abs().max(dy.abs());
+        match self {
+            FollowRule::Signum => FollowRule::Slack(0).follow(node, target),
+            FollowRule::Slack(slack) => {
+                if distance >= 2 + *slack as i32 {
+                    (node.0 + dx.signum(), node.1 + dy.signum())
+                } else {
+                    node
+                }
+            }
+            FollowRule::Cardinal => {
+                let mut node = node;
+                while (target.0 - node.0).abs().max((target.1 - node.1).abs()) >= 2 {
+                    let (dx, dy) = (target.0 - node.0, target.1 - node.1);
+                    if dx.abs() >= dy.abs() {
+                        node.0 += dx.signum();
+                    } else {
+                        node.1 += dy.signum();
                     }
                 }
+                node
+            }
+        }
+    }
+}
+
+pub type Position = (i32, i32);
+
+pub struct Rope {
+    knots: Vec<Position>, // First element is the head, last element is the tail.
+    rule: FollowRule,
+}
+
+impl Rope {
+    pub fn new(length: usize) -> Self {
+        Rope::with_rule(length, FollowRule::Signum)
+    }
+
+    pub fn with_rule(length: usize, rule: FollowRule) -> Self {
+        assert!(length > 0, "A rope must have at least one knot");
+        Rope {
+            knots: vec![(0, 0); length],
+            rule,
+        }
+    }
+
+    pub fn knots(&self) -> &[Position] {
+        &self.knots
+    }
+
+    pub fn step(&mut self, direction: &Direction) {
+        // 1) Move the head.
+        let (dx, dy) = direction.delta();
+        let h = &mut self.knots[0];
+        *h = (h.0 + dx, h.1 + dy);
+
+        // 2) Move the rest of the rope.
+        for i in 1..self.knots.len() {
+            self.knots[i] = self.rule.follow(self.knots[i], self.knots[i - 1]);
+        }
+    }
+
+    // Applies all the movements and returns the position of each knot after each step.
+    pub fn simulate(&mut self, movements: &[Movement]) -> Trajectory {
+        let mut states = vec![self.knots.clone()];
+        for m in movements {
+            for _ in 0..m.distance {
+                self.step(&m.direction);
+                states.push(self.knots.clone());
             }
         }
+        Trajectory { states }
+    }
+}
+
+pub struct Trajectory {
+    states: Vec<Vec<Position>>, // The first state is the initial one.
+}
+
+impl Trajectory {
+    pub fn states(&self) -> &[Vec<Position>] {
+        &self.states
+    }
+
+    // Knot 0 is the head.
+    pub fn visited(&self, knot: usize) -> HashSet<Position> {
+        self.states.iter().map(|knots| knots[knot]).collect()
+    }
+
+    pub fn nb_positions_visited(&self, knot: usize) -> usize {
+        self.visited(knot).len()
+    }
+
+    // One line per knot and per step: "step,knot,x,y".
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("step,knot,x,y\n");
+        for (step, knots) in self.states.iter().enumerate() {
+            for (knot, (x, y)) in knots.iter().enumerate() {
+                csv += &format!("{},{},{},{}\n", step, knot, x, y);
+            }
+        }
+        csv
+    }
+}
+
+// Top left and bottom right corners.
+pub type Bounds = (Position, Position);
+
+impl Trajectory {
+    // The smallest bounds containing all the knots at every step and the start.
+    pub fn bounds(&self) -> Bounds {
+        self.states
+            .iter()
+            .flatten()
+            .fold(((0, 0), (0, 0)), |((x1, y1), (x2, y2)), (x, y)| {
+                ((x1.min(*x), y1.min(*y)), (x2.max(*x), y2.max(*y)))
+            })
+    }
+
+    // Same style as the puzzle: 'H' for the head, the knot numbers or 'T' for the tail of a rope
+    // of two knots, 's' for the start. A knot hides the ones behind it.
+    pub fn render_frame(&self, step: usize, bounds: Bounds) -> String {
+        let knots = &self.states[step];
+        render(bounds, |p| {
+            knots.iter().position(|k| *k == p).map(|i| match i {
+                0 => 'H',
+                1 if knots.len() == 2 => 'T',
+                1..=9 => char::from_digit(i as u32, 10).unwrap(),
+                _ => '*',
+            })
+        })
+    }
+
+    // '#' for the positions visited by the knot, 's' for the start.
+    pub fn render_visited(&self, knot: usize, bounds: Bounds) -> String {
+        let visited = self.visited(knot);
+        render(bounds, |p| visited.contains(&p).then_some('#'))
+    }
+}
+
+fn render(bounds: Bounds, cell: impl Fn(Position) -> Option<char>) -> String {
+    let ((x1, y1), (x2, y2)) = bounds;
+    let mut ascii = String::new();
+    for y in y1..=y2 {
+        for x in x1..=x2 {
+            ascii.push(match cell((x, y)) {
+                Some(c) if c != '#' || (x, y) != (0, 0) => c,
+                _ if (x, y) == (0, 0) => 's',
+                _ => '.',
+            });
+        }
+        ascii += "\n";
+    }
+    ascii
+}
+
+// Same as 'Trajectory::nb_positions_visited' for the tail without keeping the states.
+pub fn nb_positions_visited_by_tail(movements: &[Movement], rope_length: usize) -> usize {
+    let mut rope = Rope::new(rope_length);
+    let mut visited: HashSet<Position> = HashSet::new();
+    visited.insert(rope.knots()[rope_length - 1]);
+    for m in movements {
+        for _ in 0..m.distance {
+            rope.step(&m.direction);
+            visited.insert(rope.knots()[rope_length - 1]);
+        }
     }
     visited.len()
 }
@@ -84,13 +328,13 @@ mod tests {
     #[test]
     fn part1() {
         let movements = parse(MOVEMENTS);
-        assert_eq!(nb_positions_visited_by_tail::<2>(&movements), 13);
+        assert_eq!(nb_positions_visited_by_tail(&movements, 2), 13);
     }
 
     #[test]
     fn part2() {
         let movements = parse(MOVEMENTS);
-        assert_eq!(nb_positions_visited_by_tail::<10>(&movements), 1);
+        assert_eq!(nb_positions_visited_by_tail(&movements, 10), 1);
 
         let movements_2 = parse(
             "R 5
@@ -102,6 +346,97 @@ mod tests {
             L 25
             U 20",
         );
-        assert_eq!(nb_positions_visited_by_tail::<10>(&movements_2), 36);
+        assert_eq!(nb_positions_visited_by_tail(&movements_2, 10), 36);
+    }
+
+    #[test]
+    fn trajectory() {
+        let movements = parse(MOVEMENTS);
+        let trajectory = Rope::new(3).simulate(&movements);
+        assert_eq!(trajectory.states().len(), 25);
+        assert_eq!(trajectory.states()[4], vec![(4, 0), (3, 0), (2, 0)]);
+        assert_eq!(trajectory.nb_positions_visited(1), 13);
+        assert_eq!(trajectory.nb_positions_visited(0), 21);
+
+        let csv = trajectory.to_csv();
+        assert!(csv.starts_with("step,knot,x,y\n0,0,0,0\n0,1,0,0\n0,2,0,0\n1,0,1,0\n"));
+        assert_eq!(csv.lines().count(), 1 + 25 * 3);
+    }
+
+    #[test]
+    fn rendering() {
+        let movements = parse(MOVEMENTS);
+        let trajectory = Rope::new(2).simulate(&movements);
+        let bounds = trajectory.bounds();
+        assert_eq!(bounds, ((0, -4), (5, 0)));
+        assert_eq!(
+            trajectory.render_frame(4, bounds),
+            "......
+......
+......
+......
+s..TH.
+"
+        );
+        assert_eq!(
+            trajectory.render_visited(1, bounds),
+            "..##..
+...##.
+.####.
+....#.
+s###..
+"
+        );
+
+        let trajectory = Rope::new(10).simulate(&movements);
+        assert_eq!(
+            trajectory.render_frame(8, ((0, -4), (5, 0))),
+            "....H.
+....1.
+..432.
+.5....
+6.....
+"
+        );
+    }
+
+    #[test]
+    fn scripts_and_rules() {
+        let movements = parse_script("UR 2, D 1\n(L 1, DL 1) * 2\n").unwrap();
+        assert_eq!(
+            movements
+                .iter()
+                .map(Movement::to_string)
+                .collect::<Vec<String>>(),
+            vec!["UR 2", "D 1", "L 1", "DL 1", "L 1", "DL 1"]
+        );
+        assert_eq!(
+            Rope::new(2).simulate(&movements).states()[2],
+            vec![(2, -2), (1, -1)]
+        );
+        assert!(parse_script("R 1\nX 3").unwrap_err().starts_with("Line 2"));
+        assert!(parse_script("(R 1) * x").is_err());
+        assert!(parse_script("U 2\nR -3").unwrap_err().starts_with("Line 2"));
+
+        let movements = parse(MOVEMENTS);
+        assert_eq!(
+            Rope::with_rule(2, FollowRule::Slack(0))
+                .simulate(&movements)
+                .nb_positions_visited(1),
+            13
+        );
+        let mut rope = Rope::with_rule(2, FollowRule::Slack(2));
+        rope.simulate(&parse("R 3"));
+        assert_eq!(rope.knots(), [(3, 0), (0, 0)]);
+        rope.simulate(&parse("R 1"));
+        assert_eq!(rope.knots(), [(4, 0), (1, 0)]);
+
+        let mut rope = Rope::with_rule(2, FollowRule::Cardinal);
+        rope.simulate(&parse("R 1\nU 1\nU 1"));
+        assert_eq!(rope.knots(), [(1, -2), (0, -1)]);
+        rope.simulate(&parse("UR 1"));
+        assert_eq!(rope.knots(), [(2, -3), (1, -2)]);
+        rope.simulate(&parse("UR 100"));
+        assert_eq!(rope.knots(), [(102, -103), (101, -102)]);
     }
 }
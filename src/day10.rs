@@ -1,9 +1,47 @@
+use std::{
+    fmt,
+    io::{self, Write},
+};
+
+use crate::ocr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Register {
+    X,
+    Y,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Instruction {
     Noop,
     Addx(i32),
+    Addy(i32),
+    Jmp(i32),             // Relative to the current instruction.
+    SkipIfZero(Register), // Skips the next instruction if the register is 0.
+    SkipIfNotZero(Register),
+}
+
+impl Instruction {
+    pub fn cycles(&self) -> usize {
+        match self {
+            Instruction::Addx(_) | Instruction::Addy(_) => 2,
+            Instruction::Noop
+            | Instruction::Jmp(_)
+            | Instruction::SkipIfZero(_)
+            | Instruction::SkipIfNotZero(_) => 1,
+        }
+    }
 }
 
 pub fn parse(input: &str) -> Vec<Instruction> {
+    fn register(s: &str) -> Register {
+        match s {
+            "x" => Register::X,
+            "y" => Register::Y,
+            other => panic!("Unknown register: {}", other),
+        }
+    }
+
     input
         .lines()
         .map(|l| {
@@ -11,64 +49,365 @@ pub fn parse(input: &str) -> Vec<Instruction> {
             match split[0] {
                 "noop" => Instruction::Noop,
                 "addx" => Instruction::Addx(split[1].parse::<i32>().unwrap()),
+                "addy" => Instruction::Addy(split[1].parse::<i32>().unwrap()),
+                "jmp" => Instruction::Jmp(split[1].parse::<i32>().unwrap()),
+                "skz" => Instruction::SkipIfZero(register(split[1])),
+                "sknz" => Instruction::SkipIfNotZero(register(split[1])),
                 other => panic!("Unknown instruction: {}", other),
             }
         })
         .collect()
 }
 
-pub struct Screen {
-    screen: Vec<Vec<bool>>,
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Registers {
+    pub x: i32,
+    pub y: i32,
 }
 
-impl Screen {
-    pub fn new() -> Self {
-        Screen { screen: Vec::new() }
+impl Registers {
+    pub fn get(&self, r: Register) -> i32 {
+        match r {
+            Register::X => self.x,
+            Register::Y => self.y,
+        }
+    }
+}
+
+// State of the CPU during a cycle, the first cycle is 1.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cycle {
+    pub number: usize,
+    pub pc: usize,
+    pub registers: Registers,
+}
+
+pub trait CycleObserver {
+    fn observe(&mut self, cycle: &Cycle);
+}
+
+pub struct Cpu<'a> {
+    program: &'a [Instruction],
+    registers: Registers,
+    pc: usize,
+    cycle: usize,     // Number of cycles done.
+    remaining: usize, // Remaining cycles of the current instruction.
+}
+
+impl<'a> Cpu<'a> {
+    pub fn new(program: &'a [Instruction]) -> Self {
+        Cpu {
+            program,
+            registers: Registers { x: 1, y: 0 },
+            pc: 0,
+            cycle: 0,
+            remaining: 0,
+        }
     }
 
-    pub fn draw_screen(&mut self, instructions: &[Instruction]) -> i32 {
-        let mut x = 1; // Middle sprite position, sprite is 3 pixels wide.
-        let mut signal_strength = 0;
-        let mut cycle = 0;
+    pub fn registers(&self) -> Registers {
+        self.registers
+    }
 
-        let mut tick = |x: &i32| {
-            let pos_x = cycle % 40;
-            let pos_y = cycle / 40;
-            if pos_x == 0 {
-                self.screen.push(vec![false; 40])
-            }
+    pub fn pc(&self) -> usize {
+        self.pc
+    }
 
-            if pos_x >= x - 1 && pos_x <= x + 1 {
-                self.screen[pos_y as usize][pos_x as usize] = true;
-            }
+    pub fn cycle(&self) -> usize {
+        self.cycle
+    }
+
+    pub fn is_halted(&self) -> bool {
+        self.pc >= self.program.len()
+    }
 
-            cycle += 1;
+    // Executes one cycle and returns the state during it, the effect of an instruction is
+    // applied at the end of its last cycle. Returns 'None' once the program is finished.
+    pub fn step(&mut self) -> Option<Cycle> {
+        let instruction = *self.program.get(self.pc)?;
+        if self.remaining == 0 {
+            self.remaining = instruction.cycles();
+        }
+
+        self.cycle += 1;
+        let cycle = Cycle {
+            number: self.cycle,
+            pc: self.pc,
+            registers: self.registers,
+        };
+
+        self.remaining -= 1;
+        if self.remaining == 0 {
+            self.execute(instruction);
+        }
+        Some(cycle)
+    }
 
-            if (cycle - 20) % 40 == 0 {
-                signal_strength += x * cycle;
+    fn execute(&mut self, instruction: Instruction) {
+        let next = match instruction {
+            Instruction::Noop => 1,
+            Instruction::Addx(v) => {
+                self.registers.x += v;
+                1
+            }
+            Instruction::Addy(v) => {
+                self.registers.y += v;
+                1
+            }
+            Instruction::Jmp(offset) => offset as isize,
+            Instruction::SkipIfZero(r) => {
+                if self.registers.get(r) == 0 {
+                    2
+                } else {
+                    1
+                }
+            }
+            Instruction::SkipIfNotZero(r) => {
+                if self.registers.get(r) != 0 {
+                    2
+                } else {
+                    1
+                }
             }
         };
+        // A jump before the first instruction halts the program.
+        self.pc = self.pc.checked_add_signed(next).unwrap_or(usize::MAX);
+    }
 
-        for i in instructions {
-            match i {
-                Instruction::Noop => tick(&x),
-                Instruction::Addx(v) => {
-                    tick(&x);
-                    tick(&x);
-                    x += *v;
+    // Runs until the end of the program, which may never happen with 'jmp', see 'run_for'.
+    pub fn run(&mut self, observers: &mut [&mut dyn CycleObserver]) {
+        self.run_for(observers, usize::MAX);
+    }
+
+    // Runs at most 'max_cycles' cycles. Returns 'true' if the program is finished.
+    pub fn run_for(&mut self, observers: &mut [&mut dyn CycleObserver], max_cycles: usize) -> bool {
+        for _ in 0..max_cycles {
+            match self.step() {
+                Some(cycle) => {
+                    for o in observers.iter_mut() {
+                        o.observe(&cycle);
+                    }
                 }
+                None => break,
+            }
+        }
+        self.is_halted()
+    }
+}
+
+// Cycles 'first', 'first + period', 'first + 2 * period', ... A period of 0 only probes 'first'.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProbeSchedule {
+    pub first: usize,
+    pub period: usize,
+}
+
+pub const DEFAULT_PROBES: ProbeSchedule = ProbeSchedule {
+    first: 20,
+    period: 40,
+};
+
+impl ProbeSchedule {
+    pub fn contains(&self, cycle: usize) -> bool {
+        cycle >= self.first && (cycle - self.first).is_multiple_of(self.period)
+    }
+}
+
+// Sum of the signal strengths during the probed cycles.
+pub struct SignalStrength {
+    pub schedule: ProbeSchedule,
+    pub sum: i32,
+}
+
+impl SignalStrength {
+    pub fn new(schedule: ProbeSchedule) -> Self {
+        SignalStrength { schedule, sum: 0 }
+    }
+}
+
+impl Default for SignalStrength {
+    fn default() -> Self {
+        SignalStrength::new(DEFAULT_PROBES)
+    }
+}
+
+impl CycleObserver for SignalStrength {
+    fn observe(&mut self, cycle: &Cycle) {
+        if self.schedule.contains(cycle.number) {
+            self.sum += cycle.registers.x * cycle.number as i32;
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Geometry {
+    pub width: usize,
+    pub height: usize,
+    pub sprite_width: usize,
+}
+
+pub const CRT: Geometry = Geometry {
+    width: 40,
+    height: 6,
+    sprite_width: 3,
+};
+
+impl Geometry {
+    pub fn nb_pixels(&self) -> usize {
+        self.width * self.height
+    }
+}
+
+// The number of cycles doesn't match the number of pixels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScreenError {
+    Overflow { nb_pixels: usize },
+    Incomplete { nb_cycles: usize, nb_pixels: usize },
+}
+
+impl fmt::Display for ScreenError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScreenError::Overflow { nb_pixels } => {
+                write!(
+                    f,
+                    "The program runs past the last pixel ({} pixels)",
+                    nb_pixels
+                )
             }
+            ScreenError::Incomplete {
+                nb_cycles,
+                nb_pixels,
+            } => write!(
+                f,
+                "The program finished after {} cycles, {} pixels were not drawn",
+                nb_cycles,
+                nb_pixels - nb_cycles
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ScreenError {}
+
+pub struct Screen {
+    geometry: Geometry,
+    screen: Vec<Vec<bool>>,
+    nb_cycles: usize,
+}
+
+impl CycleObserver for Screen {
+    // Cycles after the last pixel are only counted.
+    fn observe(&mut self, cycle: &Cycle) {
+        self.nb_cycles += 1;
+        let lit = self.is_lit(cycle);
+        let (pos_x, pos_y) = self.pixel_position(cycle);
+        if let Some(row) = self.screen.get_mut(pos_y) {
+            row[pos_x] = lit;
+        }
+    }
+}
+
+impl Screen {
+    pub fn new(geometry: Geometry) -> Self {
+        assert!(
+            geometry.width > 0 && geometry.height > 0,
+            "A screen must have at least one pixel"
+        );
+        Screen {
+            geometry,
+            screen: vec![vec![false; geometry.width]; geometry.height],
+            nb_cycles: 0,
+        }
+    }
+
+    pub fn geometry(&self) -> Geometry {
+        self.geometry
+    }
+
+    pub fn pixels(&self) -> &[Vec<bool>] {
+        &self.screen
+    }
+
+    // Pixel drawn during the cycle, the row is beyond the screen after the last pixel.
+    pub fn pixel_position(&self, cycle: &Cycle) -> (usize, usize) {
+        (
+            (cycle.number - 1) % self.geometry.width,
+            (cycle.number - 1) / self.geometry.width,
+        )
+    }
+
+    // First and last pixels of the sprite, it's centered on X. An even sprite has one more pixel
+    // on the right.
+    pub fn sprite(&self, cycle: &Cycle) -> (i32, i32) {
+        let first = cycle.registers.x - (self.geometry.sprite_width as i32 - 1) / 2;
+        (first, first + self.geometry.sprite_width as i32 - 1)
+    }
+
+    pub fn is_lit(&self, cycle: &Cycle) -> bool {
+        let (first, last) = self.sprite(cycle);
+        (first..=last).contains(&(self.pixel_position(cycle).0 as i32))
+    }
+
+    // Returns the sum of the signal strengths. The program is stopped one cycle after the last
+    // pixel, a program finishing early is only reported by 'check'.
+    pub fn draw_screen(&mut self, instructions: &[Instruction]) -> Result<i32, ScreenError> {
+        self.draw_screen_with_probes(instructions, DEFAULT_PROBES)
+    }
+
+    pub fn draw_screen_with_probes(
+        &mut self,
+        instructions: &[Instruction],
+        probes: ProbeSchedule,
+    ) -> Result<i32, ScreenError> {
+        let mut signal_strength = SignalStrength::new(probes);
+        let max_cycles = self.geometry.nb_pixels() + 1;
+        Cpu::new(instructions).run_for(&mut [self, &mut signal_strength], max_cycles);
+        match self.check() {
+            Err(error @ ScreenError::Overflow { .. }) => Err(error),
+            _ => Ok(signal_strength.sum),
+        }
+    }
+
+    // Checks that the program drew exactly one frame.
+    pub fn check(&self) -> Result<(), ScreenError> {
+        let nb_pixels = self.geometry.nb_pixels();
+        let nb_cycles = self.nb_cycles;
+        if nb_cycles > nb_pixels {
+            Err(ScreenError::Overflow { nb_pixels })
+        } else if nb_cycles < nb_pixels {
+            Err(ScreenError::Incomplete {
+                nb_cycles,
+                nb_pixels,
+            })
+        } else {
+            Ok(())
         }
+    }
 
-        signal_strength
+    // The letters drawn on the screen, see 'ocr::recognize'.
+    pub fn to_text(&self) -> Option<String> {
+        ocr::recognize(&self.screen)
     }
 
     pub fn to_ascii(&self) -> String {
+        self.render(self.geometry.width, self.geometry.height)
+    }
+
+    // Renders the screen with the given number of columns and rows, each character is the
+    // nearest pixel.
+    pub fn render(&self, width: usize, height: usize) -> String {
         let mut ascii = String::new();
-        for line in self.screen.iter() {
-            ascii += &line
-                .iter()
-                .map(|p| if *p { '#' } else { '.' })
+        for j in 0..height {
+            let row = &self.screen[j * self.geometry.height / height];
+            ascii += &(0..width)
+                .map(|i| {
+                    if row[i * self.geometry.width / width] {
+                        '#'
+                    } else {
+                        '.'
+                    }
+                })
                 .collect::<String>();
             ascii += "\n";
         }
@@ -76,6 +415,321 @@ impl Screen {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Breakpoint {
+    Cycle(usize),
+    Register(Register, i32), // When the register changes to the given value.
+}
+
+impl Breakpoint {
+    // The registers are considered changed during the first cycle.
+    fn hit(&self, cycle: &Cycle, previous: Option<&Cycle>) -> bool {
+        match self {
+            Breakpoint::Cycle(n) => cycle.number == *n,
+            Breakpoint::Register(r, v) => {
+                cycle.registers.get(*r) == *v && previous.is_none_or(|p| p.registers.get(*r) != *v)
+            }
+        }
+    }
+}
+
+// Why 'Debugger::resume' stopped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stop {
+    Breakpoint(Breakpoint),
+    Finished,
+    Limit, // The maximum number of cycles has been run.
+}
+
+// Expression shown after each step, made of "x", "y", "cycle", "pc", "signal", integers, '+', '-',
+// '*' and parentheses.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Watch {
+    Register(Register),
+    Cycle,
+    Pc,
+    SignalStrength,
+    Const(i64),
+    Add(Box<Watch>, Box<Watch>),
+    Sub(Box<Watch>, Box<Watch>),
+    Mul(Box<Watch>, Box<Watch>),
+}
+
+impl Watch {
+    pub fn parse(expr: &str) -> Result<Self, String> {
+        let mut tokens = Vec::new();
+        let mut chars = expr.chars().peekable();
+        while let Some(&c) = chars.peek() {
+            if c.is_whitespace() {
+                chars.next();
+            } else if c.is_ascii_alphanumeric() {
+                let mut token = String::new();
+                while let Some(c) = chars.next_if(char::is_ascii_alphanumeric) {
+                    token.push(c);
+                }
+                tokens.push(token);
+            } else {
+                tokens.push(chars.next().unwrap().to_string());
+            }
+        }
+
+        let mut parser = WatchParser { tokens, pos: 0 };
+        let watch = parser.expr()?;
+        match parser.tokens.get(parser.pos) {
+            Some(token) => Err(format!("Unexpected token: {}", token)),
+            None => Ok(watch),
+        }
+    }
+
+    // Wraps on overflow.
+    pub fn eval(&self, cycle: &Cycle) -> i64 {
+        match self {
+            Watch::Register(r) => cycle.registers.get(*r) as i64,
+            Watch::Cycle => cycle.number as i64,
+            Watch::Pc => cycle.pc as i64,
+            Watch::SignalStrength => cycle.registers.x as i64 * cycle.number as i64,
+            Watch::Const(v) => *v,
+            Watch::Add(a, b) => a.eval(cycle).wrapping_add(b.eval(cycle)),
+            Watch::Sub(a, b) => a.eval(cycle).wrapping_sub(b.eval(cycle)),
+            Watch::Mul(a, b) => a.eval(cycle).wrapping_mul(b.eval(cycle)),
+        }
+    }
+}
+
+impl fmt::Display for Watch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Watch::Register(Register::X) => write!(f, "x"),
+            Watch::Register(Register::Y) => write!(f, "y"),
+            Watch::Cycle => write!(f, "cycle"),
+            Watch::Pc => write!(f, "pc"),
+            Watch::SignalStrength => write!(f, "signal"),
+            Watch::Const(v) => write!(f, "{}", v),
+            Watch::Add(a, b) => write!(f, "{} + {}", a, b),
+            // The right operand of '-' needs parentheses when it's a sum or a difference.
+            Watch::Sub(a, b) => match **b {
+                Watch::Add(..) | Watch::Sub(..) => write!(f, "{} - ({})", a, b),
+                _ => write!(f, "{} - {}", a, b),
+            },
+            Watch::Mul(a, b) => {
+                for (i, operand) in [a, b].into_iter().enumerate() {
+                    if i == 1 {
+                        write!(f, " * ")?;
+                    }
+                    match **operand {
+                        Watch::Add(..) | Watch::Sub(..) => write!(f, "({})", operand)?,
+                        _ => write!(f, "{}", operand)?,
+                    }
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+// Recursive descent as for the operations of day 11, '*' has precedence over '+' and '-'.
+struct WatchParser {
+    tokens: Vec<String>,
+    pos: usize,
+}
+
+impl WatchParser {
+    fn next(&mut self) -> Option<&str> {
+        let token = self.tokens.get(self.pos)?;
+        self.pos += 1;
+        Some(token)
+    }
+
+    fn next_if(&mut self, token: &str) -> bool {
+        if self.tokens.get(self.pos).is_some_and(|t| t == token) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expr(&mut self) -> Result<Watch, String> {
+        let mut watch = self.term()?;
+        loop {
+            if self.next_if("+") {
+                watch = Watch::Add(Box::new(watch), Box::new(self.term()?));
+            } else if self.next_if("-") {
+                watch = Watch::Sub(Box::new(watch), Box::new(self.term()?));
+            } else {
+                return Ok(watch);
+            }
+        }
+    }
+
+    fn term(&mut self) -> Result<Watch, String> {
+        let mut watch = self.factor()?;
+        while self.next_if("*") {
+            watch = Watch::Mul(Box::new(watch), Box::new(self.factor()?));
+        }
+        Ok(watch)
+    }
+
+    fn factor(&mut self) -> Result<Watch, String> {
+        match self.next() {
+            Some("x") => Ok(Watch::Register(Register::X)),
+            Some("y") => Ok(Watch::Register(Register::Y)),
+            Some("cycle") => Ok(Watch::Cycle),
+            Some("pc") => Ok(Watch::Pc),
+            Some("signal") => Ok(Watch::SignalStrength),
+            Some("(") => {
+                let watch = self.expr()?;
+                if self.next_if(")") {
+                    Ok(watch)
+                } else {
+                    Err(String::from("Missing ')'"))
+                }
+            }
+            Some(token) => token
+                .parse()
+                .map(Watch::Const)
+                .map_err(|_| format!("Unexpected token: {}", token)),
+            None => Err(String::from("Unexpected end of expression")),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TraceEntry {
+    pub cycle: usize,
+    pub x: i32,
+    pub pixel: (usize, usize),
+    pub lit: bool,
+    pub sprite: (i32, i32),
+}
+
+// Runs a program step by step while drawing the screen and tracing each cycle.
+pub struct Debugger<'a> {
+    cpu: Cpu<'a>,
+    screen: Screen,
+    breakpoints: Vec<Breakpoint>,
+    watches: Vec<Watch>,
+    trace: Vec<TraceEntry>,
+    last_cycle: Option<Cycle>,
+}
+
+impl<'a> Debugger<'a> {
+    pub fn new(program: &'a [Instruction]) -> Self {
+        Debugger::with_geometry(program, CRT)
+    }
+
+    pub fn with_geometry(program: &'a [Instruction], geometry: Geometry) -> Self {
+        Debugger {
+            cpu: Cpu::new(program),
+            screen: Screen::new(geometry),
+            breakpoints: Vec::new(),
+            watches: Vec::new(),
+            trace: Vec::new(),
+            last_cycle: None,
+        }
+    }
+
+    pub fn cpu(&self) -> &Cpu<'a> {
+        &self.cpu
+    }
+
+    pub fn screen(&self) -> &Screen {
+        &self.screen
+    }
+
+    pub fn last_cycle(&self) -> Option<Cycle> {
+        self.last_cycle
+    }
+
+    pub fn trace(&self) -> &[TraceEntry] {
+        &self.trace
+    }
+
+    pub fn add_breakpoint(&mut self, breakpoint: Breakpoint) {
+        self.breakpoints.push(breakpoint);
+    }
+
+    pub fn remove_breakpoint(&mut self, breakpoint: Breakpoint) {
+        self.breakpoints.retain(|b| *b != breakpoint);
+    }
+
+    pub fn add_watch(&mut self, watch: Watch) {
+        self.watches.push(watch);
+    }
+
+    // Values of the watches during the last executed cycle.
+    pub fn watch_values(&self) -> Vec<(Watch, i64)> {
+        match self.last_cycle {
+            Some(cycle) => self
+                .watches
+                .iter()
+                .map(|w| (w.clone(), w.eval(&cycle)))
+                .collect(),
+            None => Vec::new(),
+        }
+    }
+
+    // Returns 'None' when the program is finished.
+    pub fn step_cycle(&mut self) -> Option<Cycle> {
+        let cycle = self.cpu.step()?;
+        self.screen.observe(&cycle);
+        self.trace.push(TraceEntry {
+            cycle: cycle.number,
+            x: cycle.registers.x,
+            pixel: self.screen.pixel_position(&cycle),
+            lit: self.screen.is_lit(&cycle),
+            sprite: self.screen.sprite(&cycle),
+        });
+        self.last_cycle = Some(cycle);
+        Some(cycle)
+    }
+
+    // Executes all the cycles of the current instruction.
+    pub fn step_instruction(&mut self) -> Option<Cycle> {
+        let pc = self.cpu.pc();
+        let mut cycle = self.step_cycle()?;
+        while self.cpu.pc() == pc && self.cpu.remaining > 0 {
+            cycle = self.step_cycle()?;
+        }
+        Some(cycle)
+    }
+
+    // Runs until a breakpoint is hit during a cycle, at most 'max_cycles' cycles as a program can
+    // loop forever.
+    pub fn resume(&mut self, max_cycles: usize) -> Stop {
+        for _ in 0..max_cycles {
+            let previous = self.last_cycle;
+            let Some(cycle) = self.step_cycle() else {
+                return Stop::Finished;
+            };
+            if let Some(b) = self
+                .breakpoints
+                .iter()
+                .find(|b| b.hit(&cycle, previous.as_ref()))
+            {
+                return Stop::Breakpoint(*b);
+            }
+        }
+        Stop::Limit
+    }
+
+    // CSV: "cycle,x,pixel_x,pixel_y,lit,sprite_first,sprite_last".
+    pub fn write_trace<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        writeln!(
+            writer,
+            "cycle,x,pixel_x,pixel_y,lit,sprite_first,sprite_last"
+        )?;
+        for t in self.trace.iter() {
+            writeln!(
+                writer,
+                "{},{},{},{},{},{},{}",
+                t.cycle, t.x, t.pixel.0, t.pixel.1, t.lit, t.sprite.0, t.sprite.1
+            )?;
+        }
+        Ok(())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -230,17 +884,18 @@ mod tests {
     #[test]
     fn part1() {
         let instructions = parse(INSTRUCTION);
-        let mut screen = Screen::new();
-        assert_eq!(screen.draw_screen(&instructions), 13140);
+        let mut screen = Screen::new(CRT);
+        assert_eq!(screen.draw_screen(&instructions), Ok(13140));
     }
 
     #[test]
     fn part2() {
         let instructions = parse(INSTRUCTION);
-        let mut screen = Screen::new();
-        screen.draw_screen(&instructions);
+        let mut screen = Screen::new(CRT);
+        screen.draw_screen(&instructions).unwrap();
         println!("{}", screen.to_ascii());
 
+        assert_eq!(screen.to_text(), None);
         assert_eq!(
             screen.to_ascii(),
             "##..##..##..##..##..##..##..##..##..##..
@@ -252,4 +907,181 @@ mod tests {
 "
         );
     }
+
+    #[test]
+    fn cpu() {
+        let program = parse(
+            "noop
+            addx 3
+            addx -5",
+        );
+        let mut cpu = Cpu::new(&program);
+        let xs: Vec<i32> = std::iter::from_fn(|| cpu.step())
+            .map(|c| c.registers.x)
+            .collect();
+        assert_eq!(xs, vec![1, 1, 1, 4, 4]);
+        assert_eq!(cpu.registers().x, -1);
+        assert!(cpu.is_halted());
+        assert_eq!(cpu.step(), None);
+
+        // Counts down Y from 3 to 0.
+        let program = parse(
+            "addy 3
+            skz y
+            jmp 2
+            jmp 3
+            addy -1
+            jmp -4
+            addx 10",
+        );
+        let mut cpu = Cpu::new(&program);
+        cpu.run(&mut []);
+        assert_eq!(cpu.registers(), Registers { x: 11, y: 0 });
+        assert_eq!(cpu.cycle(), 21);
+    }
+
+    #[test]
+    fn geometry() {
+        let instructions = parse(INSTRUCTION);
+        let mut screen = Screen::new(CRT);
+        let probes = ProbeSchedule {
+            first: 60,
+            period: 40,
+        };
+        assert_eq!(
+            screen.draw_screen_with_probes(&instructions, probes),
+            Ok(13140 - 420)
+        );
+        assert_eq!(screen.check(), Ok(()));
+        assert_eq!(
+            screen.render(10, 3),
+            "##########
+#.#.#.#.#.
+##.##.##.#
+"
+        );
+
+        let mut screen = Screen::new(Geometry {
+            width: 20,
+            height: 6,
+            sprite_width: 1,
+        });
+        assert_eq!(
+            screen.draw_screen(&instructions),
+            Err(ScreenError::Overflow { nb_pixels: 120 })
+        );
+        assert_eq!(
+            screen.check(),
+            Err(ScreenError::Overflow { nb_pixels: 120 })
+        );
+        assert_eq!(
+            screen.to_ascii().lines().next(),
+            Some(".#...#..#...#....#..")
+        );
+
+        let mut screen = Screen::new(Geometry {
+            width: 40,
+            height: 8,
+            sprite_width: 3,
+        });
+        assert_eq!(screen.draw_screen(&instructions), Ok(13140));
+        assert_eq!(
+            screen.check(),
+            Err(ScreenError::Incomplete {
+                nb_cycles: 240,
+                nb_pixels: 320
+            })
+        );
+        assert_eq!(
+            screen.to_ascii().lines().last(),
+            Some(".".repeat(40).as_str())
+        );
+
+        // Never finishes.
+        assert_eq!(
+            Screen::new(CRT).draw_screen(&parse("noop\njmp 0")),
+            Err(ScreenError::Overflow { nb_pixels: 240 })
+        );
+    }
+
+    #[test]
+    fn debugger() {
+        let instructions = parse(INSTRUCTION);
+        let mut debugger = Debugger::new(&instructions);
+        debugger.add_breakpoint(Breakpoint::Cycle(20));
+        debugger.add_breakpoint(Breakpoint::Register(Register::X, 20));
+        debugger.add_watch(Watch::SignalStrength);
+        debugger.add_watch(Watch::parse("(x - 1) * 2 + cycle").unwrap());
+
+        assert_eq!(
+            debugger.resume(1000),
+            Stop::Breakpoint(Breakpoint::Cycle(20))
+        );
+        assert_eq!(
+            debugger.watch_values(),
+            vec![
+                (Watch::SignalStrength, 420),
+                (Watch::parse("(x - 1) * 2 + cycle").unwrap(), 20 * 2 + 20)
+            ]
+        );
+        assert_eq!(
+            debugger.resume(1000),
+            Stop::Breakpoint(Breakpoint::Register(Register::X, 20))
+        );
+        assert_eq!(debugger.cpu().cycle(), 22);
+
+        debugger.remove_breakpoint(Breakpoint::Register(Register::X, 20));
+        // Ends 'addx 5' then executes 'addx -1'.
+        assert_eq!(debugger.step_instruction().unwrap().number, 23);
+        assert_eq!(debugger.step_cycle().unwrap().number, 24);
+        assert_eq!(debugger.step_instruction().unwrap().number, 25);
+        assert_eq!(debugger.cpu().registers().x, 24);
+        assert_eq!(debugger.resume(1000), Stop::Finished);
+        assert_eq!(debugger.screen().to_ascii(), {
+            let mut screen = Screen::new(CRT);
+            screen.draw_screen(&instructions).unwrap();
+            screen.to_ascii()
+        });
+
+        let mut trace = Vec::new();
+        debugger.write_trace(&mut trace).unwrap();
+        let trace = String::from_utf8(trace).unwrap();
+        assert_eq!(trace.lines().count(), 241);
+        assert_eq!(trace.lines().nth(3), Some("3,16,2,0,false,15,17"));
+
+        // X is 20 during the cycles 3 to 7, it only breaks when it changes.
+        let instructions = parse("addx 19\nnoop\nnoop\nnoop\naddx 1");
+        let mut debugger = Debugger::new(&instructions);
+        debugger.add_breakpoint(Breakpoint::Register(Register::X, 20));
+        assert_eq!(
+            debugger.resume(1000),
+            Stop::Breakpoint(Breakpoint::Register(Register::X, 20))
+        );
+        assert_eq!(debugger.cpu().cycle(), 3);
+        assert_eq!(debugger.resume(1000), Stop::Finished);
+
+        let instructions = parse("noop\njmp 0");
+        let mut debugger = Debugger::new(&instructions);
+        assert_eq!(debugger.resume(1000), Stop::Limit);
+        assert_eq!(debugger.cpu().cycle(), 1000);
+    }
+
+    #[test]
+    fn watches() {
+        let watch = Watch::parse("signal - 2 * (x + pc) - (cycle - y)").unwrap();
+        assert_eq!(watch.to_string(), "signal - 2 * (x + pc) - (cycle - y)");
+        let cycle = Cycle {
+            number: 20,
+            pc: 3,
+            registers: Registers { x: 21, y: 5 },
+        };
+        assert_eq!(watch.eval(&cycle), 420 - 2 * (21 + 3) - (20 - 5));
+        assert_eq!(Watch::parse(" x*x ").unwrap().eval(&cycle), 441);
+
+        assert!(Watch::parse("z").is_err());
+        assert!(Watch::parse("x / 2").is_err());
+        assert!(Watch::parse("(x + 1").is_err());
+        assert!(Watch::parse("x +").is_err());
+        assert!(Watch::parse("").is_err());
+    }
 }
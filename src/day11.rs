@@ -1,41 +1,255 @@
-use std::str::Lines;
+use std::{collections::HashMap, fmt, str::Lines};
 
 use itertools::Itertools;
+use rayon::prelude::*;
 
-#[derive(Debug, Clone)]
-enum Operation {
-    MulOld,
-    Mul(u64),
-    Add(u64),
+// Right side of "new = ...", made of 'old', constants, '+', '-', '*' and parentheses.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Operation {
+    Old,
+    Const(u64),
+    Add(Box<Operation>, Box<Operation>),
+    Sub(Box<Operation>, Box<Operation>),
+    Mul(Box<Operation>, Box<Operation>),
 }
 
 impl Operation {
-    fn parse(op: &[&str]) -> Self {
-        match op[1] {
-            "*" => {
-                if op[2] == "old" {
-                    Operation::MulOld
-                } else {
-                    Operation::Mul(op[2].parse::<u64>().unwrap())
+    pub fn parse(expr: &str) -> Result<Self, String> {
+        let mut tokens = Vec::new();
+        let mut chars = expr.chars().peekable();
+        while let Some(&c) = chars.peek() {
+            if c.is_whitespace() {
+                chars.next();
+            } else if c.is_ascii_alphanumeric() {
+                let mut token = String::new();
+                while let Some(c) = chars.next_if(char::is_ascii_alphanumeric) {
+                    token.push(c);
                 }
+                tokens.push(token);
+            } else {
+                tokens.push(chars.next().unwrap().to_string());
+            }
+        }
+
+        let mut parser = Parser { tokens, pos: 0 };
+        let operation = parser.expr()?;
+        match parser.tokens.get(parser.pos) {
+            Some(token) => Err(format!("Unexpected token: {}", token)),
+            None => Ok(operation),
+        }
+    }
+
+    // Returns 'None' if a value doesn't fit in a 'u128'. A worry level can't be negative so a
+    // subtraction going below 0 fails too, even if the final result is positive. Modulo 'm' the
+    // sign is lost: 'ModPolynomial::apply' gives the remainder of the exact value, like for
+    // "old - 5" with old = 3: 'apply' fails and 'apply' modulo 7 gives 5.
+    pub fn apply(&self, old: u128) -> Option<u128> {
+        match self {
+            Operation::Old => Some(old),
+            Operation::Const(v) => Some(*v as u128),
+            Operation::Add(a, b) => a.apply(old)?.checked_add(b.apply(old)?),
+            Operation::Sub(a, b) => a.apply(old)?.checked_sub(b.apply(old)?),
+            Operation::Mul(a, b) => a.apply(old)?.checked_mul(b.apply(old)?),
+        }
+    }
+
+    // Same as 'apply' modulo 'm', see 'ModPolynomial'.
+    pub fn to_polynomial(&self, m: u128) -> ModPolynomial {
+        fn coefficients(operation: &Operation, m: u128) -> Vec<u128> {
+            match operation {
+                Operation::Old => vec![0, 1 % m],
+                Operation::Const(v) => vec![*v as u128 % m],
+                Operation::Add(a, b) | Operation::Sub(a, b) => {
+                    let (a, b) = (coefficients(a, m), coefficients(b, m));
+                    (0..a.len().max(b.len()))
+                        .map(|i| {
+                            let (a, b) = (a.get(i).unwrap_or(&0), b.get(i).unwrap_or(&0));
+                            if matches!(operation, Operation::Add(..)) {
+                                add_mod(*a, *b, m)
+                            } else {
+                                add_mod(*a, (m - b) % m, m)
+                            }
+                        })
+                        .collect()
+                }
+                Operation::Mul(a, b) => {
+                    let (a, b) = (coefficients(a, m), coefficients(b, m));
+                    let mut product = vec![0; a.len() + b.len() - 1];
+                    for (i, a) in a.iter().enumerate() {
+                        for (j, b) in b.iter().enumerate() {
+                            product[i + j] = add_mod(product[i + j], mul_mod(*a, *b, m), m);
+                        }
+                    }
+                    product
+                }
+            }
+        }
+
+        ModPolynomial {
+            coefficients: coefficients(self, m),
+            m,
+        }
+    }
+
+    pub fn nb_old(&self) -> usize {
+        match self {
+            Operation::Old => 1,
+            Operation::Const(_) => 0,
+            Operation::Add(a, b) | Operation::Sub(a, b) | Operation::Mul(a, b) => {
+                a.nb_old() + b.nb_old()
             }
-            "+" => Operation::Add(op[2].parse::<u64>().unwrap()),
-            unknown => panic!("Unknown operation: {}", unknown),
         }
     }
+}
+
+// An operation flattened as a polynomial of 'old' with its coefficients modulo 'm'. It's evaluated
+// with Horner's method: one reduction per degree instead of one per node of the expression.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ModPolynomial {
+    coefficients: Vec<u128>, // Lowest degree first, all below 'm'.
+    m: u128,
+}
+
+impl ModPolynomial {
+    // 'old' must be below 'm'. 'acc * old + c' is below 'm^2', it fits in a 'u64' up to 2^32 and
+    // in a 'u128' up to 2^64. Beyond, the products go through 'mul_mod'.
+    pub fn apply(&self, old: u128) -> u128 {
+        let (last, rest) = self.coefficients.split_last().unwrap();
+        let m = self.m;
+        if m <= 1 << 32 {
+            let (old, m) = (old as u64, m as u64);
+            rest.iter()
+                .rev()
+                .fold(*last as u64, |acc, c| (acc * old + *c as u64) % m) as u128
+        } else if m <= 1 << 64 {
+            rest.iter().rev().fold(*last, |acc, c| (acc * old + c) % m)
+        } else {
+            rest.iter()
+                .rev()
+                .fold(*last, |acc, c| add_mod(mul_mod(acc, old, m), *c, m))
+        }
+    }
+}
+
+// 'a + b' modulo 'm' for 'a' and 'b' below 'm', 'a + b' may not fit in a 'u128'.
+fn add_mod(a: u128, b: u128, m: u128) -> u128 {
+    if a >= m - b {
+        a - (m - b)
+    } else {
+        a + b
+    }
+}
+
+// 'a * b' modulo 'm' for 'a' and 'b' below 'm'. When the product doesn't fit in a 'u128', it's
+// computed by doubling and adding.
+fn mul_mod(mut a: u128, mut b: u128, m: u128) -> u128 {
+    if let Some(product) = a.checked_mul(b) {
+        return product % m;
+    }
+    let mut product = 0;
+    while b > 0 {
+        if b & 1 == 1 {
+            product = add_mod(product, a, m);
+        }
+        a = add_mod(a, a, m);
+        b >>= 1;
+    }
+    product
+}
 
-    fn apply(&self, v: u64) -> u64 {
+impl fmt::Display for Operation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            Operation::MulOld => v * v,
-            Operation::Mul(v2) => v * v2,
-            Operation::Add(v2) => v + v2,
+            Operation::Old => write!(f, "old"),
+            Operation::Const(v) => write!(f, "{}", v),
+            Operation::Add(a, b) => write!(f, "{} + {}", a, b),
+            // The right operand of '-' needs parentheses when it's a sum or a difference.
+            Operation::Sub(a, b) => match **b {
+                Operation::Add(..) | Operation::Sub(..) => write!(f, "{} - ({})", a, b),
+                _ => write!(f, "{} - {}", a, b),
+            },
+            Operation::Mul(a, b) => {
+                for (i, operand) in [a, b].into_iter().enumerate() {
+                    if i == 1 {
+                        write!(f, " * ")?;
+                    }
+                    match **operand {
+                        Operation::Add(..) | Operation::Sub(..) => write!(f, "({})", operand)?,
+                        _ => write!(f, "{}", operand)?,
+                    }
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+// Recursive descent, '*' has precedence over '+' and '-'.
+struct Parser {
+    tokens: Vec<String>,
+    pos: usize,
+}
+
+impl Parser {
+    fn next(&mut self) -> Option<&str> {
+        let token = self.tokens.get(self.pos)?;
+        self.pos += 1;
+        Some(token)
+    }
+
+    fn next_if(&mut self, token: &str) -> bool {
+        if self.tokens.get(self.pos).is_some_and(|t| t == token) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expr(&mut self) -> Result<Operation, String> {
+        let mut operation = self.term()?;
+        loop {
+            if self.next_if("+") {
+                operation = Operation::Add(Box::new(operation), Box::new(self.term()?));
+            } else if self.next_if("-") {
+                operation = Operation::Sub(Box::new(operation), Box::new(self.term()?));
+            } else {
+                return Ok(operation);
+            }
+        }
+    }
+
+    fn term(&mut self) -> Result<Operation, String> {
+        let mut operation = self.factor()?;
+        while self.next_if("*") {
+            operation = Operation::Mul(Box::new(operation), Box::new(self.factor()?));
+        }
+        Ok(operation)
+    }
+
+    fn factor(&mut self) -> Result<Operation, String> {
+        match self.next() {
+            Some("old") => Ok(Operation::Old),
+            Some("(") => {
+                let operation = self.expr()?;
+                if self.next_if(")") {
+                    Ok(operation)
+                } else {
+                    Err(String::from("Missing ')'"))
+                }
+            }
+            Some(token) => token
+                .parse()
+                .map(Operation::Const)
+                .map_err(|_| format!("Unexpected token: {}", token)),
+            None => Err(String::from("Unexpected end of expression")),
         }
     }
 }
 
 #[derive(Debug, Clone)]
 pub struct Monkey {
-    items: Vec<u64>,
+    items: Vec<u128>,
     operation: Operation,
     divisible_test: u64,
     monkey_to_throw_if_true: usize,
@@ -55,9 +269,9 @@ impl Monkey {
         Some(Monkey {
             items: split_line(lines.next()?)
                 .skip(2)
-                .map(|v| v.parse::<u64>().unwrap())
+                .map(|v| v.parse::<u128>().unwrap())
                 .collect(),
-            operation: Operation::parse(&split_line(lines.next()?).skip(3).collect::<Vec<&str>>()),
+            operation: Operation::parse(lines.next()?.split_once('=')?.1).unwrap(),
             divisible_test: split_line(lines.next()?)
                 .nth(3)
                 .unwrap()
@@ -79,32 +293,274 @@ pub fn parse(input: &str) -> Vec<Monkey> {
     monkeys
 }
 
-pub fn run<const WORRY_DIVIDED: u64>(monkeys: &mut [Monkey], nb_rounds: u64) -> u64 {
-    let mut inspected = vec![0u64; monkeys.len()];
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WorryError {
+    // The operation of the monkey gives a worry level out of the range of 'u128'.
+    Overflow { monkey: usize, worry: u128 },
+    // The least common multiple of the divisors doesn't fit in a 'u128'.
+    BaseOverflow,
+}
+
+impl fmt::Display for WorryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WorryError::Overflow { monkey, worry } => write!(
+                f,
+                "Monkey {}: the operation overflows with a worry level of {}",
+                monkey, worry
+            ),
+            WorryError::BaseOverflow => {
+                write!(
+                    f,
+                    "The divisors have a common multiple too large for a u128"
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for WorryError {}
 
-    let base = monkeys
+// The operations only use '+', '-' and '*' so the worry levels can be reduced modulo the least
+// common multiple of the divisors without changing the tests. Dividing the worry level breaks
+// this: 'old / d mod m' depends on more than 'old mod m'. Returns 'None' when the reduction isn't
+// valid.
+pub fn reduction_base(monkeys: &[Monkey], worry_divided: u64) -> Result<Option<u128>, WorryError> {
+    fn gcd(a: u128, b: u128) -> u128 {
+        if b == 0 {
+            a
+        } else {
+            gcd(b, a % b)
+        }
+    }
+
+    if worry_divided != 1 {
+        return Ok(None);
+    }
+    monkeys
         .iter()
-        .fold(1, |product, m| product * m.divisible_test);
+        .try_fold(1u128, |lcm, m| {
+            let divisor = m.divisible_test as u128;
+            (lcm / gcd(lcm, divisor)).checked_mul(divisor)
+        })
+        .map(Some)
+        .ok_or(WorryError::BaseOverflow)
+}
+
+// Plays the rounds and returns the number of items inspected by each monkey. 'after_round' is
+// called after each round with its number (starting at 1), the monkeys and the inspections so far.
+// Each monkey inspects its items in order and the thrown items go to the end of the list.
+pub fn simulate<const WORRY_DIVIDED: u64>(
+    monkeys: &mut [Monkey],
+    nb_rounds: u64,
+    mut after_round: impl FnMut(u64, &[Monkey], &[u64]),
+) -> Result<Vec<u64>, WorryError> {
+    let mut inspected = vec![0u64; monkeys.len()];
 
-    for _ in 0..nb_rounds {
+    let base = reduction_base(monkeys, WORRY_DIVIDED)?;
+    let polynomials: Option<Vec<ModPolynomial>> = base.map(|base| {
+        for m in monkeys.iter_mut() {
+            m.items.iter_mut().for_each(|item| *item %= base);
+        }
+        monkeys
+            .iter()
+            .map(|m| m.operation.to_polynomial(base))
+            .collect()
+    });
+
+    for round in 1..=nb_rounds {
         for i in 0..monkeys.len() {
-            while let Some(item) = monkeys[i].items.pop() {
-                inspected[i] += 1;
-                let new_worry = (monkeys[i].operation.apply(item) / WORRY_DIVIDED) % base;
-                if new_worry % monkeys[i].divisible_test == 0 {
-                    monkeys[monkeys[i].monkey_to_throw_if_true]
-                        .items
-                        .push(new_worry);
+            let mut items = std::mem::take(&mut monkeys[i].items);
+            inspected[i] += items.len() as u64;
+            for item in items.drain(..) {
+                let new_worry = match &polynomials {
+                    Some(polynomials) => polynomials[i].apply(item),
+                    None => {
+                        monkeys[i]
+                            .operation
+                            .apply(item)
+                            .ok_or(WorryError::Overflow {
+                                monkey: i,
+                                worry: item,
+                            })?
+                            / WORRY_DIVIDED as u128
+                    }
+                };
+                let target = if new_worry.is_multiple_of(monkeys[i].divisible_test as u128) {
+                    monkeys[i].monkey_to_throw_if_true
                 } else {
-                    monkeys[monkeys[i].monkey_to_throw_if_false]
-                        .items
-                        .push(new_worry);
-                }
+                    monkeys[i].monkey_to_throw_if_false
+                };
+                monkeys[target].items.push(new_worry);
             }
+            // Keeps the allocation, items thrown to itself are put back.
+            items.append(&mut monkeys[i].items);
+            monkeys[i].items = items;
+        }
+        after_round(round, monkeys, &inspected);
+    }
+
+    Ok(inspected)
+}
+
+pub fn run<const WORRY_DIVIDED: u64>(
+    monkeys: &mut [Monkey],
+    nb_rounds: u64,
+) -> Result<u128, WorryError> {
+    Ok(monkey_business(
+        &simulate::<WORRY_DIVIDED>(monkeys, nb_rounds, |_, _, _| ())?,
+        2,
+    ))
+}
+
+// State of the monkeys after a round. The worry levels are reduced modulo the product of the
+// divisors when they aren't divided, see 'reduction_base'.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RoundStats {
+    pub round: u64,
+    pub items: Vec<Vec<u128>>,
+    pub inspected: Vec<u64>,
+}
+
+impl RoundStats {
+    // Same format as the puzzle: "After round 1, the monkeys are holding items ...".
+    pub fn items_to_string(&self) -> String {
+        let mut s = format!(
+            "After round {}, the monkeys are holding items with these worry levels:\n",
+            self.round
+        );
+        for (i, items) in self.items.iter().enumerate() {
+            s += &format!("Monkey {}: {}\n", i, items.iter().join(", "));
+        }
+        s
+    }
+
+    // Same format as the puzzle: "== After round 1 ==" followed by the inspection counts.
+    pub fn inspected_to_string(&self) -> String {
+        let mut s = format!("== After round {} ==\n", self.round);
+        for (i, n) in self.inspected.iter().enumerate() {
+            s += &format!("Monkey {} inspected items {} times.\n", i, n);
+        }
+        s
+    }
+}
+
+// Plays until the last given round and returns the state after each given round.
+pub fn stats<const WORRY_DIVIDED: u64>(
+    monkeys: &mut [Monkey],
+    rounds: &[u64],
+) -> Result<Vec<RoundStats>, WorryError> {
+    let mut stats = Vec::new();
+    let nb_rounds = rounds.iter().copied().max().unwrap_or(0);
+    simulate::<WORRY_DIVIDED>(monkeys, nb_rounds, |round, monkeys, inspected| {
+        if rounds.contains(&round) {
+            stats.push(RoundStats {
+                round,
+                items: monkeys.iter().map(|m| m.items.clone()).collect(),
+                inspected: inspected.to_vec(),
+            })
+        }
+    })?;
+    Ok(stats)
+}
+
+// Follows one item from the start of a round to the start of the next one. The item can be
+// inspected by several monkeys during a round when it's thrown to a monkey playing later. Returns
+// its next state, the inspecting monkeys are added to 'inspecting'.
+fn item_round(
+    monkeys: &[Monkey],
+    polynomials: &[ModPolynomial],
+    (mut monkey, mut worry): (usize, u128),
+    inspecting: &mut Vec<usize>,
+) -> (usize, u128) {
+    loop {
+        inspecting.push(monkey);
+        let m = &monkeys[monkey];
+        worry = polynomials[monkey].apply(worry);
+        let target = if worry.is_multiple_of(m.divisible_test as u128) {
+            m.monkey_to_throw_if_true
+        } else {
+            m.monkey_to_throw_if_false
+        };
+        if target <= monkey {
+            return (target, worry);
+        }
+        monkey = target;
+    }
+}
+
+// Number of inspections per monkey of one item. Its states (monkey, worry mod base) are finite
+// so they end up in a cycle which is extrapolated.
+fn item_inspections(
+    monkeys: &[Monkey],
+    polynomials: &[ModPolynomial],
+    base: u128,
+    start: (usize, u128),
+    nb_rounds: u64,
+) -> Vec<u64> {
+    let mut first_seen: HashMap<(usize, u128), usize> = HashMap::new();
+    // The inspecting monkeys of the round 'r' are 'inspecting[round_starts[r]..round_starts[r + 1]]'.
+    let mut inspecting: Vec<usize> = Vec::new();
+    let mut round_starts: Vec<usize> = vec![0];
+    let mut state = (start.0, start.1 % base);
+
+    let mut inspected = vec![0u64; monkeys.len()];
+    let mut add = |monkeys: &[usize], times: u64| {
+        for m in monkeys {
+            inspected[*m] += times;
+        }
+    };
+
+    while ((round_starts.len() - 1) as u64) < nb_rounds {
+        if let Some(&cycle_start) = first_seen.get(&state) {
+            let nb_prefix_rounds = cycle_start as u64;
+            let cycle_len = (round_starts.len() - 1 - cycle_start) as u64;
+            let remaining = nb_rounds - nb_prefix_rounds;
+            let rest = round_starts[cycle_start + (remaining % cycle_len) as usize];
+            let (prefix, cycle) = inspecting.split_at(round_starts[cycle_start]);
+            add(prefix, 1);
+            add(cycle, remaining / cycle_len);
+            add(&inspecting[round_starts[cycle_start]..rest], 1);
+            return inspected;
         }
+        first_seen.insert(state, round_starts.len() - 1);
+        state = item_round(monkeys, polynomials, state, &mut inspecting);
+        round_starts.push(inspecting.len());
     }
+    add(&inspecting, 1);
+    inspected
+}
 
-    inspected.iter().sorted().rev().take(2).product()
+// Same as 'simulate::<1>' but each item is simulated independently and in parallel. It only
+// depends on the number of states of an item, so the number of rounds can be huge.
+pub fn inspections_by_item(monkeys: &[Monkey], nb_rounds: u64) -> Result<Vec<u64>, WorryError> {
+    let base = reduction_base(monkeys, 1)?.unwrap();
+    let polynomials: Vec<ModPolynomial> = monkeys
+        .iter()
+        .map(|m| m.operation.to_polynomial(base))
+        .collect();
+    Ok(monkeys
+        .iter()
+        .enumerate()
+        .flat_map(|(i, m)| m.items.iter().map(move |item| (i, *item)))
+        .collect::<Vec<(usize, u128)>>()
+        .into_par_iter()
+        .map(|item| item_inspections(monkeys, &polynomials, base, item, nb_rounds))
+        .reduce(
+            || vec![0; monkeys.len()],
+            |a, b| a.iter().zip(b).map(|(a, b)| a + b).collect(),
+        ))
+}
+
+// Product of the 'k' largest numbers of inspections.
+pub fn monkey_business(inspected: &[u64], k: usize) -> u128 {
+    inspected
+        .iter()
+        .sorted()
+        .rev()
+        .take(k)
+        .map(|n| *n as u128)
+        .product()
 }
 
 #[cfg(test)]
@@ -142,12 +598,224 @@ mod tests {
     #[test]
     fn part1() {
         let mut monkeys = parse(MONKEYS);
-        assert_eq!(run::<3>(&mut monkeys, 20), 10605);
+        assert_eq!(run::<3>(&mut monkeys, 20), Ok(10605));
     }
 
     #[test]
     fn part2() {
         let mut monkeys = parse(MONKEYS);
-        assert_eq!(run::<1>(&mut monkeys, 10000), 2713310158);
+        assert_eq!(run::<1>(&mut monkeys, 10000), Ok(2713310158));
+    }
+
+    #[test]
+    fn operations() {
+        let operation = Operation::parse("(old + 2) * old - 3 * (old - 1)").unwrap();
+        assert_eq!(operation.nb_old(), 3);
+        assert_eq!(operation.apply(5), Some(35 - 12));
+        assert_eq!(operation.to_polynomial(7).apply(5), 23 % 7);
+        assert_eq!(operation.to_polynomial(7).apply(1), 3); // 3 - 0.
+        assert_eq!(
+            operation.to_polynomial(1000).coefficients,
+            vec![3, 1000 - 1, 1] // old^2 - old + 3.
+        );
+
+        // The sign of a subtraction is lost modulo 'm'.
+        let subtraction = Operation::parse("old - 5").unwrap();
+        assert_eq!(subtraction.apply(3), None);
+        assert_eq!(subtraction.apply(8), Some(3));
+        assert_eq!(subtraction.to_polynomial(7).apply(3), 5);
+        assert_eq!(subtraction.to_polynomial(7).apply(1), 3);
+        assert_eq!(operation.to_string(), "(old + 2) * old - 3 * (old - 1)");
+        assert_eq!(
+            Operation::parse("old - (1 - old)").unwrap().to_string(),
+            "old - (1 - old)"
+        );
+
+        assert!(Operation::parse("old / 2").is_err());
+        assert!(Operation::parse("(old + 1").is_err());
+        assert!(Operation::parse("old +").is_err());
+
+        assert_eq!(reduction_base(&parse(MONKEYS), 3), Ok(None));
+        assert_eq!(
+            reduction_base(&parse(MONKEYS), 1),
+            Ok(Some(23 * 19 * 13 * 17))
+        );
+    }
+
+    #[test]
+    fn by_item() {
+        let monkeys = parse(MONKEYS);
+        let inspected = inspections_by_item(&monkeys, 10000).unwrap();
+        assert_eq!(inspected, vec![52166, 47830, 1938, 52013]);
+        assert_eq!(monkey_business(&inspected, 2), 2713310158);
+
+        for nb_rounds in [1, 20, 1000, 100_000] {
+            assert_eq!(
+                monkey_business(&inspections_by_item(&monkeys, nb_rounds).unwrap(), 2),
+                run::<1>(&mut monkeys.clone(), nb_rounds).unwrap()
+            );
+        }
+
+        // Each item is inspected by one to four monkeys per round.
+        let nb_rounds = 1_000_000_000_000;
+        let total: u64 = inspections_by_item(&monkeys, nb_rounds)
+            .unwrap()
+            .iter()
+            .sum();
+        assert!(total >= 10 * nb_rounds && total <= 40 * nb_rounds);
+
+        // More monkeys than bits in a 'u64', each one throws to the next or skips it.
+        let monkeys = parse(
+            &(0..70)
+                .map(|i| {
+                    format!(
+                        "Monkey {}:
+                           Starting items: {}
+                           Operation: new = old * 3 + 1
+                           Test: divisible by {}
+                             If true: throw to monkey {}
+                             If false: throw to monkey {}",
+                        i,
+                        i + 1,
+                        [2, 3, 5, 7][i % 4],
+                        (i + 2) % 70,
+                        (i + 1) % 70
+                    )
+                })
+                .join("\n\n"),
+        );
+        assert_eq!(
+            inspections_by_item(&monkeys, 500).unwrap(),
+            simulate::<1>(&mut monkeys.clone(), 500, |_, _, _| ()).unwrap()
+        );
+    }
+
+    #[test]
+    fn round_stats() {
+        let part1 = stats::<3>(&mut parse(MONKEYS), &[1, 20]).unwrap();
+        assert_eq!(
+            part1[0].items_to_string(),
+            "After round 1, the monkeys are holding items with these worry levels:
+Monkey 0: 20, 23, 27, 26
+Monkey 1: 2080, 25, 167, 207, 401, 1046
+Monkey 2: \nMonkey 3: \n"
+        );
+        assert_eq!(
+            part1[1].items_to_string().lines().nth(2),
+            Some("Monkey 1: 245, 93, 53, 199, 115")
+        );
+        assert_eq!(part1[1].inspected, vec![101, 95, 7, 105]);
+        assert_eq!(monkey_business(&part1[1].inspected, 1), 105);
+        assert_eq!(monkey_business(&part1[1].inspected, 3), 105 * 101 * 95);
+
+        let part2 = stats::<1>(&mut parse(MONKEYS), &[20]).unwrap();
+        assert_eq!(
+            part2[0].inspected_to_string(),
+            "== After round 20 ==
+Monkey 0 inspected items 99 times.
+Monkey 1 inspected items 97 times.
+Monkey 2 inspected items 8 times.
+Monkey 3 inspected items 103 times.
+"
+        );
+    }
+
+    #[test]
+    fn overflow() {
+        let square = Operation::parse("old * old").unwrap();
+        assert_eq!(square.apply(1 << 64), None);
+        assert_eq!(
+            square.apply(u64::MAX as u128),
+            Some((u64::MAX as u128).pow(2))
+        );
+        assert_eq!(Operation::parse("old - 5").unwrap().apply(3), None);
+        // Computed with 'u128' because the base is larger than 2^32.
+        let m = u64::MAX as u128;
+        assert_eq!(square.to_polynomial(m).apply(m - 1), 1);
+        // Computed with 'mul_mod' because the base is larger than 2^64.
+        assert_eq!(square.to_polynomial(u128::MAX).apply(u128::MAX - 1), 1);
+        assert_eq!(
+            mul_mod(1 << 100, 1 << 100, (1 << 127) + 1),
+            (1 << 127) + 1 - (1 << 73)
+        );
+        assert_eq!(
+            add_mod(u128::MAX - 1, u128::MAX - 2, u128::MAX),
+            u128::MAX - 3
+        );
+
+        let monkey = |divisor: u64, item: u128| {
+            format!(
+                "Monkey 0:
+                   Starting items: {}
+                   Operation: new = old * old
+                   Test: divisible by {}
+                     If true: throw to monkey 0
+                     If false: throw to monkey 0",
+                item, divisor
+            )
+        };
+        assert_eq!(
+            run::<3>(&mut parse(&monkey(2, 1 << 64)), 1),
+            Err(WorryError::Overflow {
+                monkey: 0,
+                worry: 1 << 64
+            })
+        );
+        // The worry level is above 2^64 after one round.
+        assert_eq!(run::<3>(&mut parse(&monkey(2, 1 << 32)), 1), Ok(1));
+        assert_eq!(
+            run::<1>(&mut parse(&monkey(4294967311, 1 << 32)), 10),
+            Ok(10)
+        );
+
+        let monkeys = parse(&format!(
+            "{}\n\n{}",
+            monkey(4294967311, 2),
+            monkey(4294967357, 3)
+        ));
+        assert_eq!(
+            reduction_base(&monkeys, 1),
+            Ok(Some(4294967311 * 4294967357))
+        );
+        assert_eq!(run::<1>(&mut monkeys.clone(), 10), Ok(19)); // Both throw to the monkey 0.
+        let monkeys = parse(&format!(
+            "{}\n\n{}\n\n{}",
+            monkey(18446744073709551557, 1),
+            monkey(18446744073709551533, 1),
+            monkey(4294967311, 1)
+        ));
+        assert_eq!(reduction_base(&monkeys, 1), Err(WorryError::BaseOverflow));
+        let monkeys = parse(&format!("{}\n\n{}", monkey(4, 1), monkey(6, 1)));
+        assert_eq!(reduction_base(&monkeys, 1), Ok(Some(12)));
+
+        // The product of the divisors is about 2^78.
+        let primes = [23, 29, 31, 37, 41, 43, 47, 53, 59, 61, 67, 71, 73, 79];
+        let monkeys = parse(
+            &primes
+                .iter()
+                .enumerate()
+                .map(|(i, p)| {
+                    format!(
+                        "Monkey {}:
+                           Starting items: {}, {}
+                           Operation: new = old + 1
+                           Test: divisible by {}
+                             If true: throw to monkey {}
+                             If false: throw to monkey {}",
+                        i,
+                        p,
+                        i + 1,
+                        p,
+                        (i + 3) % primes.len(),
+                        (i + 1) % primes.len()
+                    )
+                })
+                .join("\n\n"),
+        );
+        assert!(reduction_base(&monkeys, 1).unwrap().unwrap() > u64::MAX as u128);
+        assert_eq!(
+            simulate::<1>(&mut monkeys.clone(), 1000, |_, _, _| ()),
+            inspections_by_item(&monkeys, 1000)
+        );
     }
 }
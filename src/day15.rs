@@ -1,6 +1,7 @@
-use itertools::Itertools;
 use regex::Regex;
 
+use crate::interval_set::IntervalSet;
+
 #[derive(Debug)]
 pub struct Sensor {
     x: i64,
@@ -48,13 +49,8 @@ pub fn parse(input: &str) -> (Vec<Sensor>, Vec<Beacon>) {
 }
 
 pub fn number_of_position_without_beacon(sensors: &[Sensor], beacons: &[Beacon], row: i64) -> i64 {
-    let nb_beacons_on_row = beacons
-        .into_iter()
-        .filter_map(|b| if b.y == row { Some(b.x) } else { None })
-        .count() as i64;
-
-    let segments = sensors
-        .into_iter()
+    let covered: IntervalSet = sensors
+        .iter()
         .filter_map(|s| {
             let dx = s.radius - (s.y - row).abs();
             if dx >= 0 {
@@ -63,21 +59,14 @@ pub fn number_of_position_without_beacon(sensors: &[Sensor], beacons: &[Beacon],
                 None
             }
         })
-        .sorted();
-
-    [(i64::MIN, i64::MIN)]
-        .into_iter()
-        .chain(segments)
-        .tuple_windows()
-        .fold(-nb_beacons_on_row + 1, |sum, (seg1, seg2)| {
-            sum + if seg2.0 > seg1.1 {
-                seg2.1 - seg2.0
-            } else if seg2.1 > seg1.1 {
-                seg2.1 - seg1.1
-            } else {
-                0
-            }
-        })
+        .collect();
+
+    let nb_beacons_on_row = beacons
+        .iter()
+        .filter(|b| b.y == row && covered.contains(b.x))
+        .count() as i64;
+
+    covered.len() - nb_beacons_on_row
 }
 
 pub fn tuning_frequency(sensors: &[Sensor], limit: i64) -> i64 {
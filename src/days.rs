@@ -1,6 +1,9 @@
 use std::{
     fs,
-    io::{BufReader, Seek, SeekFrom},
+    io::{self, BufReader, Seek, SeekFrom, Write},
+    path::Path,
+    thread,
+    time::Duration,
 };
 
 use crate::*;
@@ -48,8 +51,8 @@ pub fn day04() -> String {
 pub fn day05() -> String {
     let (mut stacks, moves) = day05::parse(&fs::read_to_string("data/day05.input").unwrap());
     let mut stacks2 = stacks.clone();
-    day05::apply_moves_by_crate_mover_9000(&mut stacks, &moves);
-    day05::apply_moves_by_crate_mover_9001(&mut stacks2, &moves);
+    day05::apply_moves(&mut stacks, &moves, &day05::CRATE_MOVER_9000);
+    day05::apply_moves(&mut stacks2, &moves, &day05::CRATE_MOVER_9001);
     format!(
         "part1: {}, part2: {}",
         day05::get_top_as_string(&stacks),
@@ -57,12 +60,37 @@ pub fn day05() -> String {
     )
 }
 
+pub fn day05_with_crane(crane: day05::Crane) -> String {
+    let (mut stacks, moves) = day05::parse(&fs::read_to_string("data/day05.input").unwrap());
+    day05::apply_moves(&mut stacks, &moves, &crane);
+    format!("crane {}: {}", crane, day05::get_top_as_string(&stacks))
+}
+
+pub fn day05_animation(crane: day05::Crane) {
+    let (stacks, moves) = day05::parse(&fs::read_to_string("data/day05.input").unwrap());
+    let mut simulator = day05::Simulator::new(stacks, &moves, crane);
+    print_frame(&day05::to_drawing(simulator.state()));
+    loop {
+        let m = simulator.next_move().map(|m| m.to_string());
+        match simulator.step() {
+            Ok(Some(stacks)) => {
+                print_frame(&format!("{}\n{}", m.unwrap(), day05::to_drawing(stacks)))
+            }
+            Ok(None) => break,
+            Err(error) => {
+                println!("{}", error);
+                break;
+            }
+        }
+    }
+}
+
 pub fn day06() -> String {
     let signals = fs::read_to_string("data/day06.input").unwrap();
     format!(
         "part1: {}, part2: {}",
-        day06::first_marker_pos(&signals, 4),
-        day06::first_marker_pos(&signals, 14)
+        day06::first_marker_pos(&signals, day06::PACKET_MARKER_SIZE).unwrap(),
+        day06::first_marker_pos(&signals, day06::MESSAGE_MARKER_SIZE).unwrap()
     )
 }
 
@@ -96,32 +124,164 @@ pub fn day08() -> String {
     )
 }
 
+// Writes the heights and the scenic scores as PGM, the visibility and the scores as PPM with the
+// best tree in red, then prints the scores heatmap.
+pub fn day08_export(dir: &Path) {
+    let forest = day08::parse(&fs::read_to_string("data/day08.input").unwrap());
+    let scores = day08::scenic_scores(&forest);
+    let best = day08::best_tree(&scores);
+    let heat_color = day08::heat_color(day08::best_scenic_score(&forest));
+
+    if let Err(error) = fs::create_dir_all(dir) {
+        println!("Unable to create {}: {}", dir.display(), error);
+        return;
+    }
+    let images = [
+        ("heights.pgm", day08::to_pgm(&forest)),
+        ("scores.pgm", day08::to_pgm(&scores)),
+        (
+            "visibility.ppm",
+            day08::to_ppm(
+                &day08::visibility_map(&forest),
+                day08::visibility_color,
+                best,
+            ),
+        ),
+        ("scores.ppm", day08::to_ppm(&scores, &heat_color, best)),
+    ];
+    for (name, image) in images {
+        let path = dir.join(name);
+        match fs::write(&path, image) {
+            Ok(()) => println!("{} written", path.display()),
+            Err(error) => println!("Unable to write {}: {}", path.display(), error),
+        }
+    }
+    print!("{}", day08::to_ansi(&forest, &scores, heat_color, best));
+}
+
 pub fn day09() -> String {
     let movements = day09::parse(&fs::read_to_string("data/day09.input").unwrap());
     format!(
         "part1: {}, part2: {}",
-        day09::nb_positions_visited_by_tail::<2>(&movements),
-        day09::nb_positions_visited_by_tail::<10>(&movements)
+        day09::nb_positions_visited_by_tail(&movements, 2),
+        day09::nb_positions_visited_by_tail(&movements, 10)
     )
 }
 
+pub fn day09_animation() {
+    const WINDOW: (i32, i32) = (40, 15); // Half width and half height around the head.
+
+    let movements = day09::parse(&fs::read_to_string("data/day09.input").unwrap());
+    let trajectory = day09::Rope::new(10).simulate(&movements);
+    let mut step = 0;
+    for m in movements.iter() {
+        step += m.distance() as usize;
+        let (x, y) = trajectory.states()[step][0];
+        let bounds = ((x - WINDOW.0, y - WINDOW.1), (x + WINDOW.0, y + WINDOW.1));
+        print_frame(&format!(
+            "== {} ==\n\n{}",
+            m,
+            trajectory.render_frame(step, bounds)
+        ));
+    }
+    println!("\n{}", trajectory.render_visited(9, trajectory.bounds()));
+}
+
 pub fn day10() -> String {
     let instructions = day10::parse(&fs::read_to_string("data/day10.input").unwrap());
-    let mut screen = day10::Screen::new();
-    let sum_signal_strength = screen.draw_screen(&instructions);
+    let mut screen = day10::Screen::new(day10::CRT);
+    let sum_signal_strength = screen.draw_screen(&instructions).unwrap();
     format!(
-        "part1: {}, part2: \n{}",
+        "part1: {}, part2: {}",
         sum_signal_strength,
-        screen.to_ascii()
+        screen
+            .to_text()
+            .unwrap_or_else(|| format!("\n{}", screen.to_ascii()))
     )
 }
 
+// Commands: "s" (cycle), "n" (instruction), "c" (continue), "b <cycle>", "bx <value>",
+// "w <expression>" (with x, y, cycle, pc, signal, '+', '-', '*' and parentheses), "p" (screen),
+// "t <file>" (trace) and "q".
+pub fn day10_debugger() {
+    let instructions = day10::parse(&fs::read_to_string("data/day10.input").unwrap());
+    let mut debugger = day10::Debugger::new(&instructions);
+
+    let print_state = |debugger: &day10::Debugger, cycle: Option<day10::Cycle>| {
+        match cycle {
+            Some(c) => println!(
+                "cycle {}, pc {} ({:?}), x = {}, y = {}",
+                c.number, c.pc, instructions[c.pc], c.registers.x, c.registers.y
+            ),
+            None => println!("Program finished after {} cycles", debugger.cpu().cycle()),
+        }
+        for (watch, value) in debugger.watch_values() {
+            println!("  {} = {}", watch, value);
+        }
+    };
+
+    let mut line = String::new();
+    loop {
+        print!("> ");
+        io::stdout().flush().unwrap();
+        line.clear();
+        if io::stdin().read_line(&mut line).unwrap() == 0 {
+            break;
+        }
+        let words: Vec<&str> = line.split_whitespace().collect();
+        match words.as_slice() {
+            ["s"] => {
+                let cycle = debugger.step_cycle();
+                print_state(&debugger, cycle)
+            }
+            ["n"] => {
+                let cycle = debugger.step_instruction();
+                print_state(&debugger, cycle)
+            }
+            // Stops after a frame without breakpoint in case of an infinite loop.
+            ["c"] => match debugger.resume(debugger.screen().geometry().nb_pixels()) {
+                day10::Stop::Breakpoint(b) => {
+                    println!("Breakpoint: {:?}", b);
+                    print_state(&debugger, debugger.last_cycle())
+                }
+                day10::Stop::Finished => print_state(&debugger, None),
+                day10::Stop::Limit => {
+                    println!("No breakpoint hit during a frame");
+                    print_state(&debugger, debugger.last_cycle())
+                }
+            },
+            ["b", n] => match n.parse() {
+                Ok(n) => debugger.add_breakpoint(day10::Breakpoint::Cycle(n)),
+                Err(_) => println!("Invalid cycle: {}", n),
+            },
+            ["bx", v] => match v.parse() {
+                Ok(v) => {
+                    debugger.add_breakpoint(day10::Breakpoint::Register(day10::Register::X, v))
+                }
+                Err(_) => println!("Invalid value: {}", v),
+            },
+            ["w", ..] => match day10::Watch::parse(&line.trim()[1..]) {
+                Ok(w) => debugger.add_watch(w),
+                Err(error) => println!("Invalid expression: {}", error),
+            },
+            ["p"] => print!("{}", debugger.screen().to_ascii()),
+            ["t", path] => match fs::File::create(path).and_then(|f| debugger.write_trace(f)) {
+                Ok(()) => println!("{} cycles written to {}", debugger.trace().len(), path),
+                Err(error) => println!("Unable to write the trace: {}", error),
+            },
+            ["q"] => break,
+            [] => (),
+            _ => println!("Unknown command: {}", line.trim()),
+        }
+    }
+}
+
 pub fn day11() -> String {
     let monkeys = day11::parse(&fs::read_to_string("data/day11.input").unwrap());
     format!(
         "part1: {}, part2: {}",
-        day11::run::<3>(&mut monkeys.clone(), 20),
-        day11::run::<1>(&mut monkeys.clone(), 10000)
+        day11::run::<3>(&mut monkeys.clone(), 20).unwrap(),
+        day11::run::<1>(&mut monkeys.clone(), 10000).unwrap()
     )
 }
 
@@ -188,3 +348,12 @@ pub fn day18() -> String {
         day18::surface_without_trapped_air(surface, obsidian)
     )
 }
+
+const FRAME_DURATION: Duration = Duration::from_millis(20);
+
+// Clears the terminal and moves the cursor to the top left before printing.
+fn print_frame(frame: &str) {
+    print!("\x1b[2J\x1b[H{}", frame);
+    let _ = io::stdout().flush();
+    thread::sleep(FRAME_DURATION);
+}
@@ -0,0 +1,204 @@
+// Set of integers stored as closed intervals [start, end].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct IntervalSet {
+    // Sorted, disjoint and non-adjacent.
+    intervals: Vec<(i64, i64)>,
+}
+
+impl IntervalSet {
+    pub fn new() -> Self {
+        IntervalSet {
+            intervals: Vec::new(),
+        }
+    }
+
+    pub fn from_interval(start: i64, end: i64) -> Self {
+        let mut set = IntervalSet::new();
+        set.insert(start, end);
+        set
+    }
+
+    pub fn intervals(&self) -> &[(i64, i64)] {
+        &self.intervals
+    }
+
+    // Merges with the overlapping and adjacent intervals, O(n).
+    pub fn insert(&mut self, start: i64, end: i64) {
+        if start > end {
+            return;
+        }
+
+        // Every interval in [i, j) overlaps or touches [start, end].
+        let i = self
+            .intervals
+            .partition_point(|&(_, e)| e < start.saturating_sub(1));
+        let j = self
+            .intervals
+            .partition_point(|&(s, _)| s <= end.saturating_add(1));
+
+        let merged = if i < j {
+            (
+                start.min(self.intervals[i].0),
+                end.max(self.intervals[j - 1].1),
+            )
+        } else {
+            (start, end)
+        };
+
+        self.intervals.splice(i..j, [merged]);
+    }
+
+    pub fn merge(&mut self, other: &IntervalSet) {
+        *self = self.union(other);
+    }
+
+    pub fn union(&self, other: &IntervalSet) -> IntervalSet {
+        self.intervals
+            .iter()
+            .chain(other.intervals.iter())
+            .copied()
+            .collect()
+    }
+
+    pub fn intersection(&self, other: &IntervalSet) -> IntervalSet {
+        let mut result = Vec::new();
+        let (mut i, mut j) = (0, 0);
+        while i < self.intervals.len() && j < other.intervals.len() {
+            let (s1, e1) = self.intervals[i];
+            let (s2, e2) = other.intervals[j];
+            let (start, end) = (s1.max(s2), e1.min(e2));
+            if start <= end {
+                result.push((start, end));
+            }
+            if e1 < e2 {
+                i += 1;
+            } else {
+                j += 1;
+            }
+        }
+        IntervalSet { intervals: result }
+    }
+
+    pub fn difference(&self, other: &IntervalSet) -> IntervalSet {
+        let mut result = Vec::new();
+        let mut j = 0;
+        for &(start, end) in self.intervals.iter() {
+            // Skip the intervals of 'other' entirely before the current one.
+            while j < other.intervals.len() && other.intervals[j].1 < start {
+                j += 1;
+            }
+            let mut k = j;
+            let mut current = Some(start);
+            while let Some(c) = current {
+                match other.intervals.get(k) {
+                    Some(&(s2, e2)) if s2 <= end => {
+                        if s2 > c {
+                            result.push((c, s2 - 1));
+                        }
+                        current = if e2 >= end { None } else { Some(c.max(e2 + 1)) };
+                        k += 1;
+                    }
+                    _ => {
+                        result.push((c, end));
+                        current = None;
+                    }
+                }
+            }
+        }
+        IntervalSet { intervals: result }
+    }
+
+    // Number of integers in the set.
+    pub fn len(&self) -> i64 {
+        self.intervals.iter().map(|(s, e)| e - s + 1).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.intervals.is_empty()
+    }
+
+    pub fn contains(&self, v: i64) -> bool {
+        self.contains_interval(v, v)
+    }
+
+    pub fn contains_interval(&self, start: i64, end: i64) -> bool {
+        let i = self.intervals.partition_point(|&(_, e)| e < end);
+        i < self.intervals.len() && self.intervals[i].0 <= start
+    }
+
+    pub fn overlaps(&self, start: i64, end: i64) -> bool {
+        let i = self.intervals.partition_point(|&(_, e)| e < start);
+        i < self.intervals.len() && self.intervals[i].0 <= end
+    }
+
+    // Uncovered intervals inside [start, end].
+    pub fn gaps(&self, start: i64, end: i64) -> IntervalSet {
+        IntervalSet::from_interval(start, end).difference(self)
+    }
+}
+
+// Intervals can be given in any order, they are sorted then merged in O(n.log(n)).
+impl FromIterator<(i64, i64)> for IntervalSet {
+    fn from_iter<I: IntoIterator<Item = (i64, i64)>>(iter: I) -> Self {
+        let mut sorted: Vec<(i64, i64)> = iter.into_iter().filter(|(s, e)| s <= e).collect();
+        sorted.sort_unstable();
+
+        let mut intervals: Vec<(i64, i64)> = Vec::with_capacity(sorted.len());
+        for (start, end) in sorted {
+            match intervals.last_mut() {
+                Some(last) if start <= last.1.saturating_add(1) => last.1 = last.1.max(end),
+                _ => intervals.push((start, end)),
+            }
+        }
+        IntervalSet { intervals }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn insert_and_merge() {
+        let mut set = IntervalSet::new();
+        set.insert(10, 12);
+        set.insert(1, 3);
+        set.insert(5, 6);
+        assert_eq!(set.intervals(), &[(1, 3), (5, 6), (10, 12)]);
+        set.insert(4, 4);
+        assert_eq!(set.intervals(), &[(1, 6), (10, 12)]);
+        set.insert(0, 20);
+        assert_eq!(set.intervals(), &[(0, 20)]);
+        assert_eq!(set.len(), 21);
+
+        let set: IntervalSet = [(8, 9), (1, 2), (2, 5), (7, 7)].into_iter().collect();
+        assert_eq!(set.intervals(), &[(1, 5), (7, 9)]);
+    }
+
+    #[test]
+    fn set_operations() {
+        let a: IntervalSet = [(0, 10), (20, 30)].into_iter().collect();
+        let b: IntervalSet = [(5, 22), (28, 40)].into_iter().collect();
+
+        assert_eq!(a.union(&b).intervals(), &[(0, 40)]);
+        assert_eq!(
+            a.intersection(&b).intervals(),
+            &[(5, 10), (20, 22), (28, 30)]
+        );
+        assert_eq!(a.difference(&b).intervals(), &[(0, 4), (23, 27)]);
+        assert_eq!(b.difference(&a).intervals(), &[(11, 19), (31, 40)]);
+        assert!(a.difference(&a).is_empty());
+    }
+
+    #[test]
+    fn queries() {
+        let set: IntervalSet = [(0, 10), (20, 30)].into_iter().collect();
+        assert!(set.contains(0) && set.contains(25) && !set.contains(15));
+        assert!(set.contains_interval(2, 8) && !set.contains_interval(8, 22));
+        assert!(set.overlaps(8, 22) && !set.overlaps(11, 19));
+        assert_eq!(
+            set.gaps(-5, 35).intervals(),
+            &[(-5, -1), (11, 19), (31, 35)]
+        );
+    }
+}
@@ -1,36 +1,34 @@
-use std::time::Instant;
+use std::{path::PathBuf, time::Instant};
 
 use clap::Parser;
 use rayon::prelude::*;
 
-mod day01;
-mod day02;
-mod day03;
-mod day04;
-mod day05;
-mod day06;
-mod day07;
-mod day08;
-mod day09;
-mod day10;
-mod day11;
-mod day12;
-mod day13;
-mod day14;
-mod day15;
-mod day16;
-mod day17;
-mod day18;
-mod days;
+use advent_of_code_2022::{day05, days};
 
 #[derive(Parser, Debug)]
 #[command(author = "Greg Burri", version = "1.0", about = "Advent of Code 2022")]
 struct Args {
-    #[arg(index(1), exclusive(true))]
+    #[arg(index(1))]
     day: Option<usize>,
 
-    #[arg(short, long)]
+    #[arg(short, long, conflicts_with("day"))]
     parallel: bool,
+
+    /// Show the step by step evolution of the given day, only some days support it.
+    #[arg(short, long, requires("day"))]
+    animate: bool,
+
+    /// Crane model for day 5: "9000", "9001" or "lift=<n|all>[,buffer]"
+    #[arg(short, long, requires("day"))]
+    crane: Option<day05::Crane>,
+
+    /// Run the program of day 10 in an interactive debugger
+    #[arg(short, long, requires("day"))]
+    debug: bool,
+
+    /// Write the images of day 8 in the given directory and print the scenic scores heatmap
+    #[arg(short, long, requires("day"))]
+    export: Option<PathBuf>,
 }
 
 fn main() {
@@ -60,6 +58,23 @@ fn main() {
     let args = Args::parse();
 
     match args.day {
+        Some(day) if args.animate => match day {
+            5 => days::day05_animation(args.crane.unwrap_or(day05::CRATE_MOVER_9000)),
+            9 => days::day09_animation(),
+            _ => println!("No animation for day {}", day),
+        },
+        Some(day) if args.debug => match day {
+            10 => days::day10_debugger(),
+            _ => println!("No debugger for day {}", day),
+        },
+        Some(day) if args.export.is_some() => match (day, args.export) {
+            (8, Some(dir)) => days::day08_export(&dir),
+            _ => println!("No export for day {}", day),
+        },
+        Some(day) if args.crane.is_some() => match (day, args.crane) {
+            (5, Some(crane)) => println!("Result of day 05: {}", days::day05_with_crane(crane)),
+            _ => println!("Crane models only apply to day 5"),
+        },
         Some(day) => {
             if day >= 1 && day <= days.len() {
                 do_day(&days, day)
@@ -0,0 +1,219 @@
+// Recognition of the capital letters drawn by some puzzles.
+
+struct Font {
+    width: usize,
+    height: usize,
+    spacing: usize,
+    glyphs: &'static [(char, &'static [&'static str])],
+}
+
+// 4x6, the font of most puzzles.
+static SMALL_FONT: Font = Font {
+    width: 4,
+    height: 6,
+    spacing: 1,
+    glyphs: &[
+        ('A', &[".##.", "#..#", "#..#", "####", "#..#", "#..#"]),
+        ('B', &["###.", "#..#", "###.", "#..#", "#..#", "###."]),
+        ('C', &[".##.", "#..#", "#...", "#...", "#..#", ".##."]),
+        ('E', &["####", "#...", "###.", "#...", "#...", "####"]),
+        ('F', &["####", "#...", "###.", "#...", "#...", "#..."]),
+        ('G', &[".##.", "#..#", "#...", "#.##", "#..#", ".###"]),
+        ('H', &["#..#", "#..#", "####", "#..#", "#..#", "#..#"]),
+        ('I', &[".###", "..#.", "..#.", "..#.", "..#.", ".###"]),
+        ('J', &["..##", "...#", "...#", "...#", "#..#", ".##."]),
+        ('K', &["#..#", "#.#.", "##..", "#.#.", "#.#.", "#..#"]),
+        ('L', &["#...", "#...", "#...", "#...", "#...", "####"]),
+        ('O', &[".##.", "#..#", "#..#", "#..#", "#..#", ".##."]),
+        ('P', &["###.", "#..#", "#..#", "###.", "#...", "#..."]),
+        ('R', &["###.", "#..#", "#..#", "###.", "#.#.", "#..#"]),
+        ('S', &[".###", "#...", "#...", ".##.", "...#", "###."]),
+        ('U', &["#..#", "#..#", "#..#", "#..#", "#..#", ".##."]),
+        ('Z', &["####", "...#", "..#.", ".#..", "#...", "####"]),
+    ],
+};
+
+// 6x10, the larger font (2018 day 10).
+static LARGE_FONT: Font = Font {
+    width: 6,
+    height: 10,
+    spacing: 2,
+    glyphs: &[
+        (
+            'A',
+            &[
+                "..##..", ".#..#.", "#....#", "#....#", "#....#", "######", "#....#", "#....#",
+                "#....#", "#....#",
+            ],
+        ),
+        (
+            'B',
+            &[
+                "#####.", "#....#", "#....#", "#....#", "#####.", "#....#", "#....#", "#....#",
+                "#....#", "#####.",
+            ],
+        ),
+        (
+            'C',
+            &[
+                ".####.", "#....#", "#.....", "#.....", "#.....", "#.....", "#.....", "#.....",
+                "#....#", ".####.",
+            ],
+        ),
+        (
+            'E',
+            &[
+                "######", "#.....", "#.....", "#.....", "#####.", "#.....", "#.....", "#.....",
+                "#.....", "######",
+            ],
+        ),
+        (
+            'F',
+            &[
+                "######", "#.....", "#.....", "#.....", "#####.", "#.....", "#.....", "#.....",
+                "#.....", "#.....",
+            ],
+        ),
+        (
+            'G',
+            &[
+                ".####.", "#....#", "#.....", "#.....", "#.....", "#..###", "#....#", "#....#",
+                "#...##", ".###.#",
+            ],
+        ),
+        (
+            'H',
+            &[
+                "#....#", "#....#", "#....#", "#....#", "######", "#....#", "#....#", "#....#",
+                "#....#", "#....#",
+            ],
+        ),
+        (
+            'J',
+            &[
+                "...###", "....#.", "....#.", "....#.", "....#.", "....#.", "....#.", "#...#.",
+                "#...#.", ".###..",
+            ],
+        ),
+        (
+            'K',
+            &[
+                "#....#", "#...#.", "#..#..", "#.#...", "##....", "##....", "#.#...", "#..#..",
+                "#...#.", "#....#",
+            ],
+        ),
+        (
+            'L',
+            &[
+                "#.....", "#.....", "#.....", "#.....", "#.....", "#.....", "#.....", "#.....",
+                "#.....", "######",
+            ],
+        ),
+        (
+            'N',
+            &[
+                "#....#", "##...#", "##...#", "#.#..#", "#.#..#", "#..#.#", "#..#.#", "#...##",
+                "#...##", "#....#",
+            ],
+        ),
+        (
+            'P',
+            &[
+                "#####.", "#....#", "#....#", "#....#", "#####.", "#.....", "#.....", "#.....",
+                "#.....", "#.....",
+            ],
+        ),
+        (
+            'R',
+            &[
+                "#####.", "#....#", "#....#", "#....#", "#####.", "#..#..", "#...#.", "#...#.",
+                "#....#", "#....#",
+            ],
+        ),
+        (
+            'X',
+            &[
+                "#....#", "#....#", ".#..#.", ".#..#.", "..##..", "..##..", ".#..#.", ".#..#.",
+                "#....#", "#....#",
+            ],
+        ),
+        (
+            'Z',
+            &[
+                "######", ".....#", ".....#", "....#.", "...#..", "..#...", ".#....", "#.....",
+                "#.....", "######",
+            ],
+        ),
+    ],
+};
+
+// The font is chosen from the number of rows, the letters must start at the first column.
+// Returns 'None' if no font has this height or if a letter is unknown.
+pub fn recognize(pixels: &[Vec<bool>]) -> Option<String> {
+    let font = [&SMALL_FONT, &LARGE_FONT]
+        .into_iter()
+        .find(|f| f.height == pixels.len())?;
+
+    let width = pixels.iter().map(Vec::len).max().unwrap_or(0);
+    let stride = font.width + font.spacing;
+    let nb_letters = (width + font.spacing) / stride;
+
+    (0..nb_letters)
+        .map(|n| {
+            let glyph: Vec<String> = pixels
+                .iter()
+                .map(|row| {
+                    (n * stride..n * stride + font.width)
+                        .map(|x| if row.get(x) == Some(&true) { '#' } else { '.' })
+                        .collect()
+                })
+                .collect();
+            font.glyphs
+                .iter()
+                .find(|(_, g)| g.iter().eq(glyph.iter()))
+                .map(|(c, _)| *c)
+        })
+        .collect()
+}
+
+// Same as 'recognize' with '#' for a lit pixel.
+pub fn recognize_ascii(ascii: &str) -> Option<String> {
+    let pixels: Vec<Vec<bool>> = ascii
+        .lines()
+        .map(|l| l.trim().chars().map(|c| c == '#').collect())
+        .collect();
+    recognize(&pixels)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn small_font() {
+        let text = "###...##...##..####.#..#.#....#..#.####.
+                    #..#.#..#.#..#.#....#.#..#....#..#.#....
+                    ###..#..#.#....###..##...#....####.###..
+                    #..#.####.#....#....#.#..#....#..#.#....
+                    #..#.#..#.#..#.#....#.#..#....#..#.#....
+                    ###..#..#..##..####.#..#.####.#..#.#....";
+        assert_eq!(recognize_ascii(text), Some(String::from("BACEKLHF")));
+        assert_eq!(recognize_ascii("####\n#..#\n"), None);
+        assert_eq!(recognize_ascii("#..#\n####\n#..#\n#..#\n#..#\n#..#"), None);
+    }
+
+    #[test]
+    fn large_font() {
+        let text = "#....#..######
+                    #....#..#.....
+                    #....#..#.....
+                    #....#..#.....
+                    ######..#####.
+                    #....#..#.....
+                    #....#..#.....
+                    #....#..#.....
+                    #....#..#.....
+                    #....#..######";
+        assert_eq!(recognize_ascii(text), Some(String::from("HE")));
+    }
+}